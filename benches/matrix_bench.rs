@@ -2,10 +2,9 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use quanta::complex::Complex;
 use quanta::matrix::QMatrix;
 use quanta::QState;
-use quanta::{constants::*, matrix};
 use rand::distributions::{Distribution, Standard};
 use rand::rngs::StdRng;
-use rand::{random, thread_rng, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
 impl Distribution<P> for Standard {
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> P {
         P(Complex {
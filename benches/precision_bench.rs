@@ -0,0 +1,49 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use quanta::complex::Real;
+use quanta::matrix::QMatrix;
+use quanta::QState;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+fn random_state<T: Real>(num_qubits: usize, rng: &mut StdRng) -> QState<T> {
+    // A layer of random single-qubit unitaries spreads the amplitudes over every basis state.
+    (0..num_qubits).fold(QState::basis_state(num_qubits, 0), |state, qubit| {
+        state.apply_on(&QMatrix::haar_random(2, rng).cast(), &[qubit])
+    })
+}
+fn gate_application<T: Real>(c: &mut Criterion, name: &str) {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut group = c.benchmark_group(format!("apply_on {name}"));
+    for num_qubits in [12, 16, 20] {
+        let state: QState<T> = random_state(num_qubits, &mut rng);
+        let single = QMatrix::haar_random(2, &mut rng).cast::<T>();
+        let double = QMatrix::haar_random(4, &mut rng).cast::<T>();
+        group.bench_with_input(
+            BenchmarkId::new("single-qubit gate", num_qubits),
+            &num_qubits,
+            |b, &n| b.iter(|| black_box(state.apply_on(&single, &[n / 2]))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("two-qubit gate", num_qubits),
+            &num_qubits,
+            |b, &n| b.iter(|| black_box(state.apply_on(&double, &[n - 1, 0]))),
+        );
+    }
+    group.finish();
+}
+fn matrix_multiplication<T: Real>(c: &mut Criterion, name: &str) {
+    let mut rng = StdRng::seed_from_u64(9);
+    let a = QMatrix::haar_random(128, &mut rng).cast::<T>();
+    let b = QMatrix::haar_random(128, &mut rng).cast::<T>();
+    c.bench_function(&format!("matrix multiplication {name}"), |bench| {
+        bench.iter(|| black_box(a.mul(&b)))
+    });
+}
+pub fn precision_bench(c: &mut Criterion) {
+    gate_application::<f32>(c, "f32");
+    gate_application::<f64>(c, "f64");
+    matrix_multiplication::<f32>(c, "f32");
+    matrix_multiplication::<f64>(c, "f64");
+}
+criterion_group!(benches, precision_bench);
+criterion_main!(benches);
@@ -0,0 +1,213 @@
+use crate::complex::Complex;
+use crate::constants::{H_GATE, I_GATE, ONE, ZERO};
+use crate::matrix::QMatrix;
+use crate::QState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    Constant,
+    Balanced,
+}
+
+/// Builds the oracle U_f|x⟩|y⟩ = |x⟩|y ⊕ f(x)⟩ for a boolean function on n bits.
+/// The output qubit y is qubit 0 and the input x occupies qubits 1..=n.
+pub fn boolean_oracle(n: usize, f: impl Fn(usize) -> bool) -> QMatrix {
+    permutation_matrix(1 << (n + 1), |index| index ^ f(index >> 1) as usize)
+}
+/// Builds the oracle for f(x) = s·x (mod 2), where s is the hidden string.
+pub fn bernstein_vazirani_oracle(n: usize, secret: usize) -> QMatrix {
+    assert!(secret < 1 << n);
+    boolean_oracle(n, |x| (x & secret).count_ones() % 2 == 1)
+}
+/// Builds the oracle U_f|x⟩|y⟩ = |x⟩|y ⊕ f(x)⟩ for a two-to-one function with f(x) = f(x ⊕ s).
+/// The output register y occupies qubits 0..n and the input x occupies qubits n..2n.
+/// A secret of zero gives a one-to-one function.
+pub fn simon_oracle(n: usize, secret: usize) -> QMatrix {
+    assert!(secret < 1 << n);
+    let mask = (1 << n) - 1;
+    permutation_matrix(1 << (2 * n), |index| {
+        let x = index >> n;
+        let fx = x.min(x ^ secret);
+        index ^ fx & mask
+    })
+}
+/// Decides whether the oracle of an n-bit function is constant or balanced with a single query.
+pub fn deutsch_jozsa(n: usize, oracle: &QMatrix) -> FunctionKind {
+    assert_eq!(oracle.dims(), 1 << (n + 1));
+    let hadamards = tensor_power(&H_GATE, n + 1);
+    let input_hadamards = I_GATE.kronecker(&tensor_power(&H_GATE, n));
+
+    let mut state = QState::basis_state(n + 1, 1)
+        .apply(&hadamards)
+        .apply(oracle)
+        .apply(&input_hadamards);
+    match measure_register(&mut state, 1, n) {
+        0 => FunctionKind::Constant,
+        _ => FunctionKind::Balanced,
+    }
+}
+/// Recovers the hidden string s of an oracle for f(x) = s·x (mod 2) with a single query.
+pub fn bernstein_vazirani(n: usize, oracle: &QMatrix) -> usize {
+    assert_eq!(oracle.dims(), 1 << (n + 1));
+    let hadamards = tensor_power(&H_GATE, n + 1);
+    let input_hadamards = I_GATE.kronecker(&tensor_power(&H_GATE, n));
+
+    let mut state = QState::basis_state(n + 1, 1)
+        .apply(&hadamards)
+        .apply(oracle)
+        .apply(&input_hadamards);
+    measure_register(&mut state, 1, n)
+}
+/// Recovers the hidden period s of a Simon oracle.
+/// Each query yields a y with y·s = 0 (mod 2). Queries are repeated until the
+/// samples span a space of dimension n - 1, after which s is the single nonzero
+/// vector in its null space over GF(2). If the samples span all n dimensions the
+/// function is one-to-one and s = 0. Panics if the oracle is not a Simon oracle and
+/// the samples fail to settle within `32·n` queries.
+pub fn simon(n: usize, oracle: &QMatrix) -> usize {
+    assert_eq!(oracle.dims(), 1 << (2 * n));
+    if n <= 1 {
+        // Every sample is y = 0, so a classical query decides between s = 1 and s = 0.
+        return (n == 1 && query(oracle, 1, 0) == query(oracle, 1, 1)) as usize;
+    }
+    let input_hadamards = tensor_power(&I_GATE, n).kronecker(&tensor_power(&H_GATE, n));
+
+    let mut basis: Vec<usize> = Vec::with_capacity(n);
+    for _ in 0..32 * n {
+        let mut state = QState::basis_state(2 * n, 0)
+            .apply(&input_hadamards)
+            .apply(oracle)
+            .apply(&input_hadamards);
+        let y = measure_register(&mut state, n, n);
+        if !insert_into_basis(&mut basis, y) {
+            continue;
+        }
+        if basis.len() == n {
+            return 0;
+        }
+        if basis.len() == n - 1 {
+            let candidate = null_space_vector(&basis, n);
+            // The samples are consistent with both s = candidate and a one-to-one
+            // function, so a classical query decides between them.
+            if query(oracle, n, 0) == query(oracle, n, candidate) {
+                return candidate;
+            }
+        }
+    }
+    panic!("the samples did not determine a period; the oracle is not a Simon oracle");
+}
+/// Measures `len` consecutive qubits starting at `offset` and packs the outcomes into an integer.
+fn measure_register(state: &mut QState, offset: usize, len: usize) -> usize {
+    (0..len).fold(0, |acc, bit| {
+        acc | (state.measure(offset + bit) as usize) << bit
+    })
+}
+/// Evaluates f(x) classically by applying the oracle to the basis state |x⟩|0⟩.
+fn query(oracle: &QMatrix, n: usize, x: usize) -> usize {
+    let state = QState::basis_state(2 * n, x << n).apply(oracle);
+    let index = (0..state.state.len())
+        .max_by(|&a, &b| state.state[a].prob().total_cmp(&state.state[b].prob()))
+        .unwrap();
+    index & ((1 << n) - 1)
+}
+/// Adds a vector to a basis kept in reduced row echelon form over GF(2).
+/// Returns false if the vector is linearly dependent on the basis.
+fn insert_into_basis(basis: &mut Vec<usize>, mut vector: usize) -> bool {
+    for &row in basis.iter() {
+        if vector >> pivot(row) & 1 == 1 {
+            vector ^= row;
+        }
+    }
+    if vector == 0 {
+        return false;
+    }
+    let new_pivot = pivot(vector);
+    for row in basis.iter_mut() {
+        if *row >> new_pivot & 1 == 1 {
+            *row ^= vector;
+        }
+    }
+    basis.push(vector);
+    true
+}
+/// Finds the nonzero solution s of row·s = 0 for a reduced basis of rank n - 1.
+fn null_space_vector(basis: &[usize], n: usize) -> usize {
+    let pivots: usize = basis.iter().map(|&row| 1 << pivot(row)).sum();
+    let free_bit = (0..n).find(|bit| pivots >> bit & 1 == 0).unwrap();
+    basis
+        .iter()
+        .filter(|&&row| row >> free_bit & 1 == 1)
+        .fold(1 << free_bit, |acc, &row| acc | 1 << pivot(row))
+}
+fn pivot(row: usize) -> usize {
+    (usize::BITS - 1 - row.leading_zeros()) as usize
+}
+fn tensor_power(gate: &QMatrix, n: usize) -> QMatrix {
+    (1..n).fold(gate.clone(), |acc, _| acc.kronecker(gate))
+}
+/// Builds the permutation matrix mapping |i⟩ to |f(i)⟩.
+fn permutation_matrix(dims: usize, f: impl Fn(usize) -> usize) -> QMatrix {
+    let mut data: Vec<Complex> = vec![ZERO; dims * dims];
+    for col in 0..dims {
+        data[f(col) * dims + col] = ONE;
+    }
+    QMatrix::from_data(data)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QuantumVec;
+
+    #[test]
+    fn deutsch_jozsa_identifies_constant_functions() {
+        for value in [false, true] {
+            let oracle = boolean_oracle(3, |_| value);
+            assert_eq!(deutsch_jozsa(3, &oracle), FunctionKind::Constant);
+        }
+    }
+    #[test]
+    fn deutsch_jozsa_identifies_balanced_functions() {
+        let parity = boolean_oracle(3, |x| x.count_ones() % 2 == 1);
+        let lowest_bit = boolean_oracle(3, |x| x & 1 == 1);
+        let highest_bit = boolean_oracle(3, |x| x >= 4);
+        for oracle in [parity, lowest_bit, highest_bit] {
+            assert_eq!(deutsch_jozsa(3, &oracle), FunctionKind::Balanced);
+        }
+    }
+    #[test]
+    fn bernstein_vazirani_recovers_every_hidden_string() {
+        for secret in 0..16 {
+            let oracle = bernstein_vazirani_oracle(4, secret);
+            assert_eq!(bernstein_vazirani(4, &oracle), secret);
+        }
+    }
+    #[test]
+    fn simon_recovers_hidden_period() {
+        for secret in [0b001, 0b101, 0b110, 0b111] {
+            let oracle = simon_oracle(3, secret);
+            assert_eq!(simon(3, &oracle), secret);
+        }
+    }
+    #[test]
+    fn simon_returns_zero_for_one_to_one_functions() {
+        let oracle = simon_oracle(3, 0);
+        assert_eq!(simon(3, &oracle), 0);
+    }
+    #[test]
+    fn simon_decides_single_bit_period() {
+        for secret in [0, 1] {
+            assert_eq!(simon(1, &simon_oracle(1, secret)), secret);
+        }
+    }
+    #[test]
+    #[should_panic(expected = "not a Simon oracle")]
+    fn simon_rejects_constant_function() {
+        simon(2, &tensor_power(&I_GATE, 4));
+    }
+    #[test]
+    fn simon_oracle_is_self_inverse() {
+        let oracle = simon_oracle(2, 0b11);
+        let identity = tensor_power(&I_GATE, 4);
+        assert!(oracle.mul(&oracle).equals(&identity));
+    }
+}
@@ -0,0 +1,391 @@
+//! A versioned binary format for checkpointing state vectors.
+//!
+//! A checkpoint is a 32-byte header followed by the raw amplitudes. The header fields are
+//! little endian:
+//!
+//! | bytes  | field                                              |
+//! |--------|----------------------------------------------------|
+//! | 0..8   | magic `QUANTAQS`                                   |
+//! | 8..10  | format version                                     |
+//! | 10     | bytes per component, 4 for f32 or 8 for f64        |
+//! | 11     | payload endianness, 0 for little or 1 for big      |
+//! | 12..16 | number of qubits                                   |
+//! | 16..24 | FNV-1a checksum of the payload                     |
+//! | 24..32 | reserved, zero                                     |
+//!
+//! The payload holds `re, im` for each amplitude in basis-index order. States are written
+//! and read in buffered chunks, so no copy of the payload is ever held in memory.
+use crate::complex::Complex;
+use crate::QState;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+const MAGIC: &[u8; 8] = b"QUANTAQS";
+pub const VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+const MAX_RESERVED_AMPLITUDES: usize = 1 << 20;
+
+/// The floating point precision of the stored amplitudes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Single,
+    Double,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointHeader {
+    pub version: u16,
+    pub num_qubits: u32,
+    pub precision: Precision,
+    pub endianness: Endianness,
+    pub checksum: u64,
+}
+/// Writes checkpoints with a chosen precision and endianness, by default f64 little endian.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckpointWriter {
+    precision: Precision,
+    endianness: Endianness,
+}
+/// A comparison of two checkpointed states.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StateDiff {
+    /// The largest magnitude of the difference between two corresponding amplitudes.
+    pub max_difference: f64,
+    /// The number of amplitudes that differ by more than the tolerance.
+    pub differing_amplitudes: usize,
+    /// The inner product ⟨a|b⟩ of the two states.
+    pub overlap: Complex,
+}
+impl StateDiff {
+    /// The fidelity |⟨a|b⟩|² between the two states.
+    pub fn fidelity(&self) -> f64 {
+        self.overlap.prob()
+    }
+}
+impl Default for CheckpointWriter {
+    fn default() -> Self {
+        CheckpointWriter::new()
+    }
+}
+impl CheckpointWriter {
+    pub fn new() -> CheckpointWriter {
+        CheckpointWriter {
+            precision: Precision::Double,
+            endianness: Endianness::Little,
+        }
+    }
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+    /// Streams the state to the writer. The amplitudes are encoded twice, once to
+    /// compute the checksum for the header and once to write them.
+    pub fn save(&self, state: &QState, writer: impl Write) -> io::Result<()> {
+        let mut checksum = FNV_OFFSET;
+        for &amplitude in &state.state {
+            self.encode(amplitude, |bytes| checksum = fnv1a(checksum, bytes));
+        }
+        let header = CheckpointHeader {
+            version: VERSION,
+            num_qubits: state.num_qubits() as u32,
+            precision: self.precision,
+            endianness: self.endianness,
+            checksum,
+        };
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(&header.to_bytes())?;
+        for &amplitude in &state.state {
+            let mut result = Ok(());
+            self.encode(amplitude, |bytes| result = writer.write_all(bytes));
+            result?;
+        }
+        writer.flush()
+    }
+    fn encode(&self, amplitude: Complex, mut sink: impl FnMut(&[u8])) {
+        match (self.precision, self.endianness) {
+            (Precision::Single, Endianness::Little) => {
+                sink(&(amplitude.re as f32).to_le_bytes());
+                sink(&(amplitude.im as f32).to_le_bytes());
+            }
+            (Precision::Single, Endianness::Big) => {
+                sink(&(amplitude.re as f32).to_be_bytes());
+                sink(&(amplitude.im as f32).to_be_bytes());
+            }
+            (Precision::Double, Endianness::Little) => {
+                sink(&amplitude.re.to_le_bytes());
+                sink(&amplitude.im.to_le_bytes());
+            }
+            (Precision::Double, Endianness::Big) => {
+                sink(&amplitude.re.to_be_bytes());
+                sink(&amplitude.im.to_be_bytes());
+            }
+        }
+    }
+}
+impl CheckpointHeader {
+    fn to_bytes(self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[0..8].copy_from_slice(MAGIC);
+        bytes[8..10].copy_from_slice(&self.version.to_le_bytes());
+        bytes[10] = match self.precision {
+            Precision::Single => 4,
+            Precision::Double => 8,
+        };
+        bytes[11] = match self.endianness {
+            Endianness::Little => 0,
+            Endianness::Big => 1,
+        };
+        bytes[12..16].copy_from_slice(&self.num_qubits.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+    fn from_bytes(bytes: &[u8; HEADER_LEN]) -> io::Result<CheckpointHeader> {
+        if &bytes[0..8] != MAGIC {
+            return Err(invalid_data("not a state checkpoint"));
+        }
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported checkpoint version {version}"
+            )));
+        }
+        let precision = match bytes[10] {
+            4 => Precision::Single,
+            8 => Precision::Double,
+            other => return Err(invalid_data(format!("invalid component size {other}"))),
+        };
+        let endianness = match bytes[11] {
+            0 => Endianness::Little,
+            1 => Endianness::Big,
+            other => return Err(invalid_data(format!("invalid endianness flag {other}"))),
+        };
+        let num_qubits = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+        let header = CheckpointHeader {
+            version,
+            num_qubits,
+            precision,
+            endianness,
+            checksum: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        };
+        // The length of the payload in bytes must fit in a usize.
+        let payload_len = 1_usize
+            .checked_shl(num_qubits)
+            .and_then(|len| len.checked_mul(2 * header.component_size()));
+        if payload_len.is_none() {
+            return Err(invalid_data(format!("{num_qubits} qubits is too many")));
+        }
+        Ok(header)
+    }
+    fn component_size(&self) -> usize {
+        match self.precision {
+            Precision::Single => 4,
+            Precision::Double => 8,
+        }
+    }
+    fn decode(&self, bytes: &[u8]) -> f64 {
+        match (self.precision, self.endianness) {
+            (Precision::Single, Endianness::Little) => {
+                f32::from_le_bytes(bytes.try_into().unwrap()) as f64
+            }
+            (Precision::Single, Endianness::Big) => {
+                f32::from_be_bytes(bytes.try_into().unwrap()) as f64
+            }
+            (Precision::Double, Endianness::Little) => {
+                f64::from_le_bytes(bytes.try_into().unwrap())
+            }
+            (Precision::Double, Endianness::Big) => f64::from_be_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+/// Reads the amplitudes of a checkpoint one at a time, verifying the checksum at the end.
+struct AmplitudeReader<R: Read> {
+    reader: BufReader<R>,
+    header: CheckpointHeader,
+    remaining: usize,
+    checksum: u64,
+}
+impl<R: Read> AmplitudeReader<R> {
+    fn new(reader: R) -> io::Result<AmplitudeReader<R>> {
+        let mut reader = BufReader::new(reader);
+        let header = read_header(&mut reader)?;
+        Ok(AmplitudeReader {
+            reader,
+            header,
+            remaining: 1 << header.num_qubits,
+            checksum: FNV_OFFSET,
+        })
+    }
+    fn next(&mut self) -> io::Result<Option<Complex>> {
+        if self.remaining == 0 {
+            if self.checksum != self.header.checksum {
+                return Err(invalid_data("checksum mismatch"));
+            }
+            return Ok(None);
+        }
+        let size = self.header.component_size();
+        let mut bytes = [0; 16];
+        self.reader.read_exact(&mut bytes[..2 * size])?;
+        self.checksum = fnv1a(self.checksum, &bytes[..2 * size]);
+        self.remaining -= 1;
+        Ok(Some(Complex {
+            re: self.header.decode(&bytes[..size]),
+            im: self.header.decode(&bytes[size..2 * size]),
+        }))
+    }
+}
+/// Reads and validates the header of a checkpoint.
+pub fn read_header(mut reader: impl Read) -> io::Result<CheckpointHeader> {
+    let mut bytes = [0; HEADER_LEN];
+    reader.read_exact(&mut bytes)?;
+    CheckpointHeader::from_bytes(&bytes)
+}
+/// Saves the state as an f64 little-endian checkpoint.
+pub fn save(state: &QState, writer: impl Write) -> io::Result<()> {
+    CheckpointWriter::new().save(state, writer)
+}
+/// Loads a state from a checkpoint, failing if the header is invalid, the payload is
+/// truncated or the checksum does not match.
+pub fn load(reader: impl Read) -> io::Result<QState> {
+    let mut amplitudes = AmplitudeReader::new(reader)?;
+    // The header is untrusted, so memory is only reserved up front for a bounded
+    // number of amplitudes and a truncated payload fails before it grows further.
+    let mut state = Vec::with_capacity(amplitudes.remaining.min(MAX_RESERVED_AMPLITUDES));
+    while let Some(amplitude) = amplitudes.next()? {
+        state.push(amplitude);
+    }
+    Ok(QState { state })
+}
+/// Compares two checkpoints amplitude by amplitude without loading either state.
+pub fn diff(a: impl Read, b: impl Read, tolerance: f64) -> io::Result<StateDiff> {
+    let mut a = AmplitudeReader::new(a)?;
+    let mut b = AmplitudeReader::new(b)?;
+    if a.header.num_qubits != b.header.num_qubits {
+        return Err(invalid_data(format!(
+            "cannot compare states on {} and {} qubits",
+            a.header.num_qubits, b.header.num_qubits
+        )));
+    }
+    let mut diff = StateDiff {
+        max_difference: 0.0,
+        differing_amplitudes: 0,
+        overlap: Complex::default(),
+    };
+    // The readers verify their checksums when they run out, which both do in the
+    // final iteration since the states have the same size.
+    while let (Some(x), Some(y)) = (a.next()?, b.next()?) {
+        let difference = (x - y).mag();
+        diff.max_difference = diff.max_difference.max(difference);
+        if difference > tolerance {
+            diff.differing_amplitudes += 1;
+        }
+        diff.overlap += x.conj() * y;
+    }
+    Ok(diff)
+}
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::matrix::QMatrix;
+    use crate::QuantumVec;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn random_state(num_qubits: usize, seed: u64) -> QState {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let unitary = QMatrix::haar_random(1 << num_qubits, &mut rng);
+        QState::basis_state(num_qubits, 0).apply(&unitary)
+    }
+    #[test]
+    fn state_round_trips_through_checkpoint() {
+        let state = random_state(5, 1);
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let mut bytes = Vec::new();
+            CheckpointWriter::new()
+                .endianness(endianness)
+                .save(&state, &mut bytes)
+                .unwrap();
+            assert_eq!(bytes.len(), HEADER_LEN + 32 * 16);
+            let header = read_header(bytes.as_slice()).unwrap();
+            assert_eq!(header.num_qubits, 5);
+            assert_eq!(header.endianness, endianness);
+            let loaded = load(bytes.as_slice()).unwrap();
+            assert_eq!(loaded.data_slice(), state.data_slice());
+        }
+    }
+    #[test]
+    fn single_precision_checkpoint_is_close() {
+        let state = random_state(4, 2);
+        let mut bytes = Vec::new();
+        CheckpointWriter::new()
+            .precision(Precision::Single)
+            .save(&state, &mut bytes)
+            .unwrap();
+        assert_eq!(bytes.len(), HEADER_LEN + 16 * 8);
+        let loaded = load(bytes.as_slice()).unwrap();
+        for (a, b) in loaded.data_slice().iter().zip(state.data_slice()) {
+            assert!((*a - *b).mag() < 1e-6);
+        }
+    }
+    #[test]
+    fn corrupted_and_truncated_checkpoints_are_rejected() {
+        let mut bytes = Vec::new();
+        save(&random_state(3, 3), &mut bytes).unwrap();
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 5] ^= 1;
+        let error = load(corrupted.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(load(&bytes[..bytes.len() - 1]).is_err());
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(load(bad_magic.as_slice()).is_err());
+    }
+    #[test]
+    fn inflated_qubit_counts_are_rejected() {
+        let mut bytes = Vec::new();
+        save(&random_state(1, 4), &mut bytes).unwrap();
+        for (num_qubits, kind) in [
+            (40_u32, io::ErrorKind::UnexpectedEof),
+            (62, io::ErrorKind::InvalidData),
+            (63, io::ErrorKind::InvalidData),
+        ] {
+            let mut inflated = bytes.clone();
+            inflated[12..16].copy_from_slice(&num_qubits.to_le_bytes());
+            assert_eq!(load(inflated.as_slice()).unwrap_err().kind(), kind);
+        }
+    }
+    #[test]
+    fn diff_reports_overlap_and_differences() {
+        let plus = QState::basis_state(2, 0).apply_on(&H_GATE, &[0]);
+        let minus = plus.apply_on(&Z_GATE, &[0]);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        save(&plus, &mut a).unwrap();
+        save(&minus, &mut b).unwrap();
+        let diff = diff(a.as_slice(), b.as_slice(), 1e-12).unwrap();
+        assert_eq!(diff.differing_amplitudes, 1);
+        assert!((diff.max_difference - 2.0 * IR2).abs() < 1e-12);
+        assert!(diff.fidelity() < 1e-12);
+
+        let same = super::diff(a.as_slice(), a.as_slice(), 1e-12).unwrap();
+        assert_eq!(same.differing_amplitudes, 0);
+        assert!((same.fidelity() - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,573 @@
+use crate::complex;
+use crate::complex::Complex;
+use crate::constants::*;
+use crate::matrix::QMatrix;
+use crate::{ClassicalBit, QState, QuantumVec};
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Gate {
+    I,
+    X,
+    Y,
+    Z,
+    H,
+    S,
+    Sdg,
+    T,
+    Tdg,
+    Sx,
+    Sxdg,
+    Rx(f64),
+    Ry(f64),
+    Rz(f64),
+    Phase(f64),
+    Cnot,
+    Cz,
+    Swap,
+    Unitary(QMatrix),
+}
+impl Gate {
+    pub fn num_qubits(&self) -> usize {
+        match self {
+            Gate::Cnot | Gate::Cz | Gate::Swap => 2,
+            Gate::Unitary(matrix) => matrix.dims().trailing_zeros() as usize,
+            _ => 1,
+        }
+    }
+    pub fn matrix(&self) -> QMatrix {
+        match self {
+            Gate::I => I_GATE.clone(),
+            Gate::X => X_GATE.clone(),
+            Gate::Y => Y_GATE.clone(),
+            Gate::Z => Z_GATE.clone(),
+            Gate::H => H_GATE.clone(),
+            Gate::S => S_GATE.clone(),
+            Gate::Sdg => S_GATE.adjoint(),
+            Gate::T => T_GATE.clone(),
+            Gate::Tdg => T_GATE.adjoint(),
+            Gate::Sx => QMatrix::from_data(vec![
+                complex!(0.5, 0.5),
+                complex!(0.5, -0.5),
+                complex!(0.5, -0.5),
+                complex!(0.5, 0.5),
+            ]),
+            Gate::Sxdg => Gate::Sx.matrix().adjoint(),
+            Gate::Rx(theta) => {
+                let (sin, cos) = (theta / 2.0).sin_cos();
+                QMatrix::from_data(vec![
+                    complex!(cos, 0.0),
+                    complex!(0.0, -sin),
+                    complex!(0.0, -sin),
+                    complex!(cos, 0.0),
+                ])
+            }
+            Gate::Ry(theta) => {
+                let (sin, cos) = (theta / 2.0).sin_cos();
+                QMatrix::from_data(vec![
+                    complex!(cos, 0.0),
+                    complex!(-sin, 0.0),
+                    complex!(sin, 0.0),
+                    complex!(cos, 0.0),
+                ])
+            }
+            Gate::Rz(theta) => {
+                let (sin, cos) = (theta / 2.0).sin_cos();
+                QMatrix::from_data(vec![complex!(cos, -sin), ZERO, ZERO, complex!(cos, sin)])
+            }
+            Gate::Phase(lambda) => {
+                let (sin, cos) = lambda.sin_cos();
+                QMatrix::from_data(vec![ONE, ZERO, ZERO, complex!(cos, sin)])
+            }
+            Gate::Cnot => CNOT_GATE.clone(),
+            Gate::Cz => CZ.clone(),
+            Gate::Swap => SWAP_GATE.clone(),
+            Gate::Unitary(matrix) => matrix.clone(),
+        }
+    }
+    pub fn inverse(&self) -> Gate {
+        match self {
+            Gate::S => Gate::Sdg,
+            Gate::Sdg => Gate::S,
+            Gate::T => Gate::Tdg,
+            Gate::Tdg => Gate::T,
+            Gate::Sx => Gate::Sxdg,
+            Gate::Sxdg => Gate::Sx,
+            Gate::Rx(theta) => Gate::Rx(-theta),
+            Gate::Ry(theta) => Gate::Ry(-theta),
+            Gate::Rz(theta) => Gate::Rz(-theta),
+            Gate::Phase(lambda) => Gate::Phase(-lambda),
+            Gate::Unitary(matrix) => Gate::Unitary(matrix.adjoint()),
+            gate => gate.clone(),
+        }
+    }
+    /// The angle of a rotation or phase gate.
+    pub fn parameter(&self) -> Option<f64> {
+        match *self {
+            Gate::Rx(angle) | Gate::Ry(angle) | Gate::Rz(angle) | Gate::Phase(angle) => Some(angle),
+            _ => None,
+        }
+    }
+    /// The same rotation or phase gate with another angle.
+    pub fn with_parameter(&self, angle: f64) -> Gate {
+        match self {
+            Gate::Rx(_) => Gate::Rx(angle),
+            Gate::Ry(_) => Gate::Ry(angle),
+            Gate::Rz(_) => Gate::Rz(angle),
+            Gate::Phase(_) => Gate::Phase(angle),
+            _ => panic!("{self:?} has no parameter"),
+        }
+    }
+    /// Whether the gate is diagonal in the computational basis.
+    pub fn is_diagonal(&self) -> bool {
+        match self {
+            Gate::I | Gate::Z | Gate::S | Gate::Sdg | Gate::T | Gate::Tdg => true,
+            Gate::Rz(_) | Gate::Phase(_) | Gate::Cz => true,
+            Gate::Unitary(matrix) => {
+                let dims = matrix.dims();
+                matrix
+                    .data_slice()
+                    .iter()
+                    .enumerate()
+                    .all(|(i, z)| i / dims == i % dims || z.mag() <= 1e-12)
+            }
+            _ => false,
+        }
+    }
+    /// Whether the gate is unchanged when its qubits are listed in reverse order.
+    pub fn is_symmetric(&self) -> bool {
+        matches!(self, Gate::Cz | Gate::Swap) || self.num_qubits() == 1
+    }
+}
+/// A contiguous range of classical bits, read as an unsigned integer with its first bit
+/// least significant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassicalRegister {
+    start: usize,
+    size: usize,
+}
+impl ClassicalRegister {
+    pub fn new(start: usize, size: usize) -> ClassicalRegister {
+        ClassicalRegister { start, size }
+    }
+    /// The register holding the single classical bit `clbit`.
+    pub fn bit(clbit: usize) -> ClassicalRegister {
+        ClassicalRegister::new(clbit, 1)
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    /// The indices of the classical bits in the register.
+    pub fn bits(&self) -> Range<usize> {
+        self.start..self.start + self.size
+    }
+    /// The integer held by the register, given the values of all classical bits.
+    pub fn value(&self, clbits: &[ClassicalBit]) -> usize {
+        clbits[self.bits()]
+            .iter()
+            .enumerate()
+            .map(|(i, &bit)| (bit as usize) << i)
+            .sum()
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction {
+    /// A gate acting on `qubits`, where `qubits[0]` is the gate's most significant qubit.
+    Gate { gate: Gate, qubits: Vec<usize> },
+    /// Measures a qubit in the computational basis and stores the outcome in a classical bit.
+    Measure { qubit: usize, clbit: usize },
+    /// A gate that is applied only when the classical register holds `value`.
+    Conditional {
+        register: ClassicalRegister,
+        value: usize,
+        gate: Gate,
+        qubits: Vec<usize>,
+    },
+    /// Prevents gates from being moved or merged across it.
+    Barrier,
+}
+impl Instruction {
+    pub fn qubits(&self) -> &[usize] {
+        match self {
+            Instruction::Gate { qubits, .. } | Instruction::Conditional { qubits, .. } => qubits,
+            Instruction::Measure { qubit, .. } => std::slice::from_ref(qubit),
+            Instruction::Barrier => &[],
+        }
+    }
+    /// The classical bits the instruction writes or reads.
+    pub fn clbits(&self) -> Range<usize> {
+        match self {
+            Instruction::Measure { clbit, .. } => *clbit..clbit + 1,
+            Instruction::Conditional { register, .. } => register.bits(),
+            _ => 0..0,
+        }
+    }
+}
+/// An ordered sequence of instructions acting on a fixed number of qubits and classical bits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circuit {
+    num_qubits: usize,
+    num_clbits: usize,
+    instructions: Vec<Instruction>,
+}
+impl Circuit {
+    pub fn new(num_qubits: usize) -> Circuit {
+        Circuit::with_clbits(num_qubits, 0)
+    }
+    pub fn with_clbits(num_qubits: usize, num_clbits: usize) -> Circuit {
+        Circuit {
+            num_qubits,
+            num_clbits,
+            instructions: Vec::new(),
+        }
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+    pub fn num_clbits(&self) -> usize {
+        self.num_clbits
+    }
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+    /// Appends a register of `size` new classical bits.
+    pub fn add_register(&mut self, size: usize) -> ClassicalRegister {
+        let register = ClassicalRegister::new(self.num_clbits, size);
+        self.num_clbits += size;
+        register
+    }
+    pub fn push(&mut self, instruction: Instruction) -> &mut Self {
+        match &instruction {
+            Instruction::Gate { gate, qubits } => self.check_gate(gate, qubits),
+            Instruction::Measure { qubit, clbit } => {
+                assert!(*qubit < self.num_qubits && *clbit < self.num_clbits);
+            }
+            Instruction::Conditional {
+                register,
+                value,
+                gate,
+                qubits,
+            } => {
+                self.check_gate(gate, qubits);
+                assert!(register.bits().end <= self.num_clbits);
+                assert!(
+                    register.size() < usize::BITS as usize && *value < 1 << register.size(),
+                    "value does not fit in the register"
+                );
+            }
+            Instruction::Barrier => {}
+        }
+        self.instructions.push(instruction);
+        self
+    }
+    fn check_gate(&self, gate: &Gate, qubits: &[usize]) {
+        assert_eq!(gate.num_qubits(), qubits.len());
+        assert!(qubits.iter().all(|&q| q < self.num_qubits));
+        let mask: usize = qubits.iter().map(|&q| 1 << q).sum();
+        assert_eq!(
+            mask.count_ones() as usize,
+            qubits.len(),
+            "qubits must be distinct"
+        );
+    }
+    pub fn add(&mut self, gate: Gate, qubits: &[usize]) -> &mut Self {
+        self.push(Instruction::Gate {
+            gate,
+            qubits: qubits.to_vec(),
+        })
+    }
+    pub fn h(&mut self, qubit: usize) -> &mut Self {
+        self.add(Gate::H, &[qubit])
+    }
+    pub fn x(&mut self, qubit: usize) -> &mut Self {
+        self.add(Gate::X, &[qubit])
+    }
+    pub fn z(&mut self, qubit: usize) -> &mut Self {
+        self.add(Gate::Z, &[qubit])
+    }
+    pub fn t(&mut self, qubit: usize) -> &mut Self {
+        self.add(Gate::T, &[qubit])
+    }
+    pub fn rx(&mut self, theta: f64, qubit: usize) -> &mut Self {
+        self.add(Gate::Rx(theta), &[qubit])
+    }
+    pub fn ry(&mut self, theta: f64, qubit: usize) -> &mut Self {
+        self.add(Gate::Ry(theta), &[qubit])
+    }
+    pub fn rz(&mut self, theta: f64, qubit: usize) -> &mut Self {
+        self.add(Gate::Rz(theta), &[qubit])
+    }
+    pub fn cnot(&mut self, control: usize, target: usize) -> &mut Self {
+        self.add(Gate::Cnot, &[control, target])
+    }
+    pub fn cz(&mut self, control: usize, target: usize) -> &mut Self {
+        self.add(Gate::Cz, &[control, target])
+    }
+    pub fn swap(&mut self, a: usize, b: usize) -> &mut Self {
+        self.add(Gate::Swap, &[a, b])
+    }
+    pub fn measure(&mut self, qubit: usize, clbit: usize) -> &mut Self {
+        self.push(Instruction::Measure { qubit, clbit })
+    }
+    /// Adds a gate that is applied only when `register` holds `value`.
+    pub fn add_if(
+        &mut self,
+        register: ClassicalRegister,
+        value: usize,
+        gate: Gate,
+        qubits: &[usize],
+    ) -> &mut Self {
+        self.push(Instruction::Conditional {
+            register,
+            value,
+            gate,
+            qubits: qubits.to_vec(),
+        })
+    }
+    pub fn barrier(&mut self) -> &mut Self {
+        self.push(Instruction::Barrier)
+    }
+    pub fn gate_count(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction,
+                    Instruction::Gate { .. } | Instruction::Conditional { .. }
+                )
+            })
+            .count()
+    }
+    /// The angles of the parameterized gates, which are the RX, RY, RZ and phase
+    /// gates outside conditionals, in instruction order.
+    pub fn parameters(&self) -> Vec<f64> {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Gate { gate, .. } => gate.parameter(),
+                _ => None,
+            })
+            .collect()
+    }
+    /// A copy of the circuit with the angles of its parameterized gates replaced, in
+    /// the order of `parameters`.
+    pub fn with_parameters(&self, parameters: &[f64]) -> Circuit {
+        let mut remaining = parameters.iter();
+        let mut circuit = self.clone();
+        for instruction in &mut circuit.instructions {
+            if let Instruction::Gate { gate, .. } = instruction {
+                if gate.parameter().is_some() {
+                    let &angle = remaining.next().expect("too few parameters");
+                    *gate = gate.with_parameter(angle);
+                }
+            }
+        }
+        assert!(remaining.next().is_none(), "too many parameters");
+        circuit
+    }
+    /// The number of layers of gates, where gates in a layer act on disjoint qubits.
+    pub fn depth(&self) -> usize {
+        let mut layers = vec![0; self.num_qubits];
+        for instruction in &self.instructions {
+            let qubits: Vec<usize> = match instruction {
+                Instruction::Barrier => (0..self.num_qubits).collect(),
+                _ => instruction.qubits().to_vec(),
+            };
+            let layer = qubits.iter().map(|&q| layers[q]).max().unwrap_or(0);
+            let next = match instruction {
+                Instruction::Barrier => layer,
+                _ => layer + 1,
+            };
+            for q in qubits {
+                layers[q] = next;
+            }
+        }
+        layers.into_iter().max().unwrap_or(0)
+    }
+    /// Applies the circuit to a state. Measurements collapse the state and their
+    /// outcomes are discarded once the circuit has run.
+    pub fn run(&self, state: &QState) -> QState {
+        self.execute(state).0
+    }
+    /// Applies the circuit to a state and returns the final state together with the
+    /// classical bits, which all start at zero. Measurements collapse the state and
+    /// store their outcome, and conditional gates read the bits as they are at that point.
+    pub fn execute(&self, state: &QState) -> (QState, Vec<ClassicalBit>) {
+        assert_eq!(state.num_qubits(), self.num_qubits);
+        let mut state = state.clone();
+        let mut clbits = vec![ClassicalBit::Off; self.num_clbits];
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate { gate, qubits } => {
+                    state = state.apply_on(&gate.matrix(), qubits);
+                }
+                Instruction::Measure { qubit, clbit } => {
+                    clbits[*clbit] = state.measure(*qubit);
+                }
+                Instruction::Conditional {
+                    register,
+                    value,
+                    gate,
+                    qubits,
+                } => {
+                    if register.value(&clbits) == *value {
+                        state = state.apply_on(&gate.matrix(), qubits);
+                    }
+                }
+                Instruction::Barrier => {}
+            }
+        }
+        (state, clbits)
+    }
+    /// The unitary matrix implemented by the circuit, built column by column.
+    pub fn unitary(&self) -> QMatrix {
+        assert!(
+            !self
+                .instructions
+                .iter()
+                .any(|instruction| !instruction.clbits().is_empty()),
+            "circuits with measurements or conditional gates have no unitary"
+        );
+        let dims = 1 << self.num_qubits;
+        let columns: Vec<QState> = (0..dims)
+            .map(|col| self.run(&QState::basis_state(self.num_qubits, col)))
+            .collect();
+        QMatrix::from_data(
+            (0..dims * dims)
+                .map(|i| columns[i % dims].data_slice()[i / dims])
+                .collect(),
+        )
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn bell_circuit_unitary_matches_matrix_product() {
+        let mut circuit = Circuit::new(2);
+        circuit.h(1).cnot(1, 0);
+        let expected_result = I_GATE.kronecker(&H_GATE).mul(&CNOT_GATE);
+        assert!(circuit.unitary().approx_eq(&expected_result, 1e-12));
+    }
+    #[test]
+    fn gate_inverses_multiply_to_identity() {
+        let gates = [
+            Gate::S,
+            Gate::T,
+            Gate::Sx,
+            Gate::Rx(0.3),
+            Gate::Ry(-1.2),
+            Gate::Rz(2.5),
+            Gate::Phase(0.7),
+            Gate::Cnot,
+            Gate::Swap,
+        ];
+        for gate in gates {
+            let product = gate.matrix().mul(&gate.inverse().matrix());
+            assert!(product.approx_eq(&QMatrix::identity(product.dims()), 1e-12));
+        }
+    }
+    #[test]
+    fn rotations_by_half_turn_match_pauli_gates_up_to_phase() {
+        assert!(Gate::Rx(PI).matrix().equals_up_to_phase(&X_GATE, 1e-12));
+        assert!(Gate::Ry(PI).matrix().equals_up_to_phase(&Y_GATE, 1e-12));
+        assert!(Gate::Rz(PI).matrix().equals_up_to_phase(&Z_GATE, 1e-12));
+        let sx_squared = Gate::Sx.matrix().mul(&Gate::Sx.matrix());
+        assert!(sx_squared.approx_eq(&X_GATE, 1e-12));
+    }
+    #[test]
+    fn depth_counts_parallel_gates_as_one_layer() {
+        let mut circuit = Circuit::new(3);
+        circuit.h(0).h(1).h(2).cnot(0, 1).x(2).barrier().z(2);
+        assert_eq!(circuit.gate_count(), 6);
+        assert_eq!(circuit.depth(), 3);
+    }
+    #[test]
+    fn teleportation_moves_state_to_third_qubit() {
+        let (theta, phi) = (0.7, 1.9);
+        let mut circuit = Circuit::new(3);
+        let z_bit = circuit.add_register(1);
+        let x_bit = circuit.add_register(1);
+        circuit
+            .ry(theta, 0)
+            .rz(phi, 0)
+            .h(1)
+            .cnot(1, 2)
+            .barrier()
+            .cnot(0, 1)
+            .h(0)
+            .measure(0, 0)
+            .measure(1, 1)
+            .add_if(x_bit, 1, Gate::X, &[2])
+            .add_if(z_bit, 1, Gate::Z, &[2]);
+        let expected = (
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+        for _ in 0..20 {
+            let (state, clbits) = circuit.execute(&QState::basis_state(3, 0));
+            assert_eq!(clbits[0], state.clone().measure(0));
+            assert_eq!(clbits[1], state.clone().measure(1));
+            let (x, y, z) = state.bloch_vector(2);
+            assert!((x - expected.0).abs() < 1e-12);
+            assert!((y - expected.1).abs() < 1e-12);
+            assert!((z - expected.2).abs() < 1e-12);
+        }
+    }
+    #[test]
+    fn conditional_gate_compares_whole_register() {
+        let mut circuit = Circuit::new(3);
+        let register = circuit.add_register(2);
+        circuit
+            .x(1)
+            .measure(0, 0)
+            .measure(1, 1)
+            .add_if(register, 1, Gate::X, &[2])
+            .add_if(register, 2, Gate::H, &[0]);
+        let (state, clbits) = circuit.execute(&QState::basis_state(3, 0));
+        assert_eq!(register.value(&clbits), 2);
+        assert_eq!(circuit.gate_count(), 3);
+        let expected = QState::basis_state(3, 2).apply_on(&H_GATE, &[0]);
+        assert!(state.equals(&expected));
+    }
+    #[test]
+    #[should_panic(expected = "value does not fit in the register")]
+    fn conditional_value_wider_than_register_panics() {
+        let mut circuit = Circuit::new(1);
+        let register = circuit.add_register(2);
+        circuit.add_if(register, 4, Gate::X, &[0]);
+    }
+    #[test]
+    fn parameters_are_rebound_in_instruction_order() {
+        let mut circuit = Circuit::new(2);
+        circuit
+            .rx(0.1, 0)
+            .h(1)
+            .cnot(0, 1)
+            .add(Gate::Phase(0.2), &[1])
+            .rz(0.3, 0);
+        assert_eq!(circuit.parameters(), vec![0.1, 0.2, 0.3]);
+        let rebound = circuit.with_parameters(&[1.0, 2.0, 3.0]);
+        assert_eq!(rebound.parameters(), vec![1.0, 2.0, 3.0]);
+        assert_eq!(rebound.instructions()[3].qubits(), &[1]);
+        assert_eq!(rebound.gate_count(), circuit.gate_count());
+    }
+    #[test]
+    #[should_panic(expected = "too few parameters")]
+    fn rebinding_too_few_parameters_panics() {
+        let mut circuit = Circuit::new(1);
+        circuit.rx(0.1, 0).ry(0.2, 0);
+        circuit.with_parameters(&[1.0]);
+    }
+    #[test]
+    #[should_panic]
+    fn adding_gate_on_repeated_qubit_panics() {
+        Circuit::new(2).cnot(1, 1);
+    }
+}
@@ -5,21 +5,122 @@ macro_rules! complex {
     }};
 }
 
-use float_cmp::approx_eq;
 use std::{
-    iter::Sum,
-    ops::{Add, AddAssign, Div, DivAssign, Mul, Neg},
+    fmt::{self, Debug},
+    iter::{Product, Sum},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
-use crate::constants::ZERO;
+/// The floating point types that amplitudes can be stored in, `f32` or `f64`.
+pub trait Real:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + Sum
+    + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+    /// The difference between 1 and the next larger representable number.
+    const EPSILON: Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn atan2(self, other: Self) -> Self;
+    fn copysign(self, sign: Self) -> Self;
+    fn is_finite(self) -> bool;
+}
+macro_rules! impl_real {
+    ($float:ty) => {
+        impl Real for $float {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            const EPSILON: Self = <$float>::EPSILON;
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value as $float
+            }
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            #[inline]
+            fn sqrt(self) -> Self {
+                <$float>::sqrt(self)
+            }
+            #[inline]
+            fn abs(self) -> Self {
+                <$float>::abs(self)
+            }
+            #[inline]
+            fn exp(self) -> Self {
+                <$float>::exp(self)
+            }
+            #[inline]
+            fn ln(self) -> Self {
+                <$float>::ln(self)
+            }
+            #[inline]
+            fn powf(self, exponent: Self) -> Self {
+                <$float>::powf(self, exponent)
+            }
+            #[inline]
+            fn sin_cos(self) -> (Self, Self) {
+                <$float>::sin_cos(self)
+            }
+            #[inline]
+            fn atan2(self, other: Self) -> Self {
+                <$float>::atan2(self, other)
+            }
+            #[inline]
+            fn copysign(self, sign: Self) -> Self {
+                <$float>::copysign(self, sign)
+            }
+            #[inline]
+            fn is_finite(self) -> bool {
+                <$float>::is_finite(self)
+            }
+        }
+        impl Mul<Complex<$float>> for $float {
+            type Output = Complex<$float>;
+            fn mul(self, rhs: Complex<$float>) -> Self::Output {
+                rhs * self
+            }
+        }
+        impl Div<Complex<$float>> for $float {
+            type Output = Complex<$float>;
+            fn div(self, rhs: Complex<$float>) -> Self::Output {
+                complex!(self, 0.0) / rhs
+            }
+        }
+    };
+}
+impl_real!(f32);
+impl_real!(f64);
 
+/// A complex number with `f64` parts by default, or `f32` parts for single precision.
 #[derive(Clone, Copy, Debug, PartialEq, Default)]
-pub struct Complex {
-    pub re: f64,
-    pub im: f64,
+#[repr(C)]
+pub struct Complex<T = f64> {
+    pub re: T,
+    pub im: T,
 }
-impl Neg for Complex {
-    type Output = Complex;
+impl<T: Real> Neg for Complex<T> {
+    type Output = Complex<T>;
     fn neg(self) -> Self::Output {
         Complex {
             re: -self.re,
@@ -27,79 +128,310 @@ impl Neg for Complex {
         }
     }
 }
-impl Add for Complex {
-    type Output = Complex;
+impl<T: Real> Add for Complex<T> {
+    type Output = Complex<T>;
     fn add(self, rhs: Self) -> Self::Output {
         complex!(self.re + rhs.re, self.im + rhs.im)
     }
 }
-impl AddAssign for Complex {
+impl<T: Real> AddAssign for Complex<T> {
     fn add_assign(&mut self, rhs: Self) {
         *self = *self + rhs;
     }
 }
-impl Mul for Complex {
-    type Output = Complex;
+impl<T: Real> Sub for Complex<T> {
+    type Output = Complex<T>;
+    fn sub(self, rhs: Self) -> Self::Output {
+        complex!(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+impl<T: Real> SubAssign for Complex<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+impl<T: Real> Mul for Complex<T> {
+    type Output = Complex<T>;
     fn mul(self, rhs: Self) -> Self::Output {
         let re = (self.re * rhs.re) - (self.im * rhs.im);
         let im = (self.im * rhs.re) + (self.re * rhs.im);
         complex!(re, im)
     }
 }
-impl Mul<f64> for Complex {
-    type Output = Complex;
-    fn mul(self, rhs: f64) -> Self::Output {
+impl<T: Real> Mul<T> for Complex<T> {
+    type Output = Complex<T>;
+    fn mul(self, rhs: T) -> Self::Output {
         complex!(self.re * rhs, self.im * rhs)
     }
 }
-impl Mul<Complex> for f64 {
-    type Output = Complex;
-    fn mul(self, rhs: Complex) -> Self::Output {
-        rhs * self
+impl<T: Real> MulAssign for Complex<T> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+impl<T: Real> MulAssign<T> for Complex<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
     }
 }
-impl DivAssign<f64> for Complex {
-    fn div_assign(&mut self, rhs: f64) {
+impl<T: Real> DivAssign<T> for Complex<T> {
+    fn div_assign(&mut self, rhs: T) {
         *self = *self / rhs;
     }
 }
-impl Div for Complex {
-    type Output = Complex;
+impl<T: Real> Div for Complex<T> {
+    type Output = Complex<T>;
     fn div(self, rhs: Self) -> Self::Output {
-        let denominator_scalar = rhs.re.powi(2) + rhs.im.powi(2);
+        let denominator_scalar = rhs.re * rhs.re + rhs.im * rhs.im;
         let re = ((self.re * rhs.re) + (self.im * rhs.im)) / denominator_scalar;
         let im = ((self.im * rhs.re) - (self.re * rhs.im)) / denominator_scalar;
         complex!(re, im)
     }
 }
-impl Div<f64> for Complex {
-    type Output = Complex;
-    fn div(self, rhs: f64) -> Self::Output {
-        self / complex!(rhs, 0.0)
+impl<T: Real> Div<T> for Complex<T> {
+    type Output = Complex<T>;
+    fn div(self, rhs: T) -> Self::Output {
+        self / complex!(rhs, T::ZERO)
     }
 }
-impl Sum for Complex {
+/// Formats as `0.5+0.5i`, dropping parts that round to zero. The precision defaults to
+/// three decimals, and trailing zeros are trimmed.
+impl<T: Real> fmt::Display for Complex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(DEFAULT_PRECISION);
+        let re = format_real(self.re.to_f64(), precision);
+        let im = format_real(self.im.to_f64(), precision);
+        match (re.as_str(), im.as_str()) {
+            (_, "0") => write!(f, "{re}"),
+            ("0", _) => write!(f, "{im}i"),
+            (_, _) if im.starts_with('-') => write!(f, "{re}{im}i"),
+            (_, _) => write!(f, "{re}+{im}i"),
+        }
+    }
+}
+pub(crate) const DEFAULT_PRECISION: usize = 3;
+/// Formats a number with at most `precision` decimals, trimming trailing zeros and
+/// never printing `-0`.
+pub(crate) fn format_real(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*}", precision, value);
+    let trimmed = match formatted.contains('.') {
+        true => formatted.trim_end_matches('0').trim_end_matches('.'),
+        false => &formatted,
+    };
+    match trimmed {
+        "-0" => "0".to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+impl<T: Real> Sum for Complex<T> {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
-        iter.fold(ZERO, |acc, e| acc + e)
+        iter.fold(Complex::zero(), |acc, e| acc + e)
     }
 }
-impl Complex {
+impl<T: Real> Product for Complex<T> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Complex::one(), |acc, e| acc * e)
+    }
+}
+impl<T: Real> Complex<T> {
+    pub fn zero() -> Complex<T> {
+        complex!(T::ZERO, T::ZERO)
+    }
+    pub fn one() -> Complex<T> {
+        complex!(T::ONE, T::ZERO)
+    }
+    /// Converts the parts to another precision.
+    pub fn cast<U: Real>(self) -> Complex<U> {
+        complex!(U::from_f64(self.re.to_f64()), U::from_f64(self.im.to_f64()))
+    }
+    /// The unit complex number e^(iθ) = cos θ + i sin θ.
+    pub fn cis(theta: T) -> Complex<T> {
+        let (sin, cos) = theta.sin_cos();
+        complex!(cos, sin)
+    }
+    pub fn from_polar(r: T, theta: T) -> Complex<T> {
+        Complex::cis(theta) * r
+    }
+    /// The magnitude and argument of the number.
+    pub fn to_polar(self) -> (T, T) {
+        (self.mag(), self.arg())
+    }
     #[inline]
-    pub fn mag(self) -> f64 {
-        (self.re.powi(2) + self.im.powi(2)).sqrt()
+    pub fn mag(self) -> T {
+        self.prob().sqrt()
     }
     #[inline]
-    pub fn conj(self) -> Complex {
+    pub fn conj(self) -> Complex<T> {
         Complex {
             re: self.re,
             im: -self.im,
         }
     }
-    pub fn prob(self) -> f64 {
-        self.mag().powi(2)
+    #[inline]
+    pub fn prob(self) -> T {
+        self.re * self.re + self.im * self.im
+    }
+    /// The argument in (-π, π].
+    #[inline]
+    pub fn arg(self) -> T {
+        self.im.atan2(self.re)
+    }
+    pub fn exp(self) -> Complex<T> {
+        Complex::from_polar(self.re.exp(), self.im)
+    }
+    /// The principal natural logarithm, with imaginary part in (-π, π].
+    pub fn ln(self) -> Complex<T> {
+        complex!(self.mag().ln(), self.arg())
+    }
+    /// Raises the number to a real power on the principal branch.
+    pub fn powf(self, exponent: T) -> Complex<T> {
+        Complex::from_polar(self.mag().powf(exponent), self.arg() * exponent)
+    }
+    /// The principal square root, with non-negative real part.
+    pub fn sqrt(self) -> Complex<T> {
+        if self == Complex::zero() {
+            return Complex::zero();
+        }
+        // Computed without going through the argument to avoid losing precision
+        // for numbers close to the real axis.
+        let two = T::ONE + T::ONE;
+        let t = ((self.re.abs() + self.mag()) / two).sqrt();
+        match self.re >= T::ZERO {
+            true => complex!(t, self.im / (two * t)),
+            false => complex!(self.im.abs() / (two * t), t.copysign(self.im)),
+        }
+    }
+    pub fn is_finite(self) -> bool {
+        self.re.is_finite() && self.im.is_finite()
     }
+    /// Whether both parts agree to within a few units in the last place of `T`, or to
+    /// within `T::EPSILON` near zero.
     #[inline]
-    pub fn equals(self, other: Complex) -> bool {
-        approx_eq!(f64, self.re, other.re) && approx_eq!(f64, self.im, other.im)
+    pub fn equals(self, other: Complex<T>) -> bool {
+        let close = |a: T, b: T| {
+            let scale = match a.abs() > b.abs() {
+                true => a.abs(),
+                false => b.abs(),
+            };
+            let difference = (a - b).abs();
+            difference <= T::EPSILON || difference <= T::from_f64(4.0) * T::EPSILON * scale
+        };
+        close(self.re, other.re) && close(self.im, other.im)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{ONE, ZERO};
+    use std::f64::consts::PI;
+
+    fn close(a: Complex, b: Complex) -> bool {
+        (a - b).mag() < 1e-12
+    }
+    fn samples() -> Vec<Complex> {
+        vec![
+            complex!(0.5, 0.5),
+            complex!(-1.5, 0.25),
+            complex!(-2.0, -3.0),
+            complex!(0.1, -4.0),
+            complex!(3.0, 0.0),
+            complex!(-3.0, 0.0),
+            complex!(0.0, 2.0),
+            complex!(0.0, -0.5),
+        ]
+    }
+    #[test]
+    fn subtraction_and_assigning_operators_match_definitions() {
+        let (a, b) = (complex!(1.5, -2.0), complex!(-0.5, 4.0));
+        assert_eq!(a - b, complex!(2.0, -6.0));
+        assert_eq!(a - b, a + -b);
+        let mut c = a;
+        c -= b;
+        assert_eq!(c, a - b);
+        let mut c = a;
+        c *= b;
+        assert_eq!(c, a * b);
+        let mut c = a;
+        c *= 2.0;
+        assert_eq!(c, complex!(3.0, -4.0));
+        assert!(close(2.0 / complex!(1.0, 1.0), complex!(1.0, -1.0)));
+        assert_eq!(
+            samples().into_iter().product::<Complex>(),
+            samples().into_iter().fold(ONE, |acc, z| acc * z)
+        );
+        assert_eq!(Vec::<Complex>::new().into_iter().product::<Complex>(), ONE);
+    }
+    #[test]
+    fn eulers_formula_holds() {
+        assert!(close(complex!(0.0, PI).exp() + ONE, ZERO));
+        for theta in [-3.0, -1.2, 0.0, 0.4, 2.5, PI] {
+            assert!(close(Complex::cis(theta), complex!(0.0, theta).exp()));
+            assert!(close(
+                Complex::cis(theta),
+                complex!(theta.cos(), theta.sin())
+            ));
+        }
+        let z = complex!(0.3, -1.1);
+        assert!(close(z.exp(), z.re.exp() * Complex::cis(z.im)));
+    }
+    #[test]
+    fn polar_form_round_trips() {
+        for z in samples() {
+            let (r, theta) = z.to_polar();
+            assert!(close(Complex::from_polar(r, theta), z));
+            assert!(theta > -PI && theta <= PI);
+        }
+        assert_eq!(complex!(-1.0, 0.0).arg(), PI);
+        assert_eq!(complex!(0.0, 1.0).arg(), PI / 2.0);
+    }
+    #[test]
+    fn exp_and_ln_are_inverse() {
+        for z in samples() {
+            assert!(close(z.ln().exp(), z));
+            assert!(close((z * 0.3).exp().ln(), z * 0.3));
+        }
+        assert!(close(complex!(-1.0, 0.0).ln(), complex!(0.0, PI)));
+        let (a, b) = (complex!(0.4, 0.2), complex!(-0.3, 0.1));
+        assert!(close((a + b).exp(), a.exp() * b.exp()));
+    }
+    #[test]
+    fn square_roots_and_powers_agree() {
+        for z in samples() {
+            let root = z.sqrt();
+            assert!(close(root * root, z));
+            assert!(root.re >= 0.0);
+            assert!(close(z.powf(0.5), root));
+            assert!(close(z.powf(2.0), z * z));
+            assert!(close(z.powf(-1.0), 1.0 / z));
+            assert!(close(z.powf(0.0), ONE));
+        }
+        assert_eq!(complex!(-4.0, 0.0).sqrt(), complex!(0.0, 2.0));
+        assert_eq!(complex!(-4.0, -0.0).sqrt(), complex!(0.0, -2.0));
+        assert_eq!(ZERO.sqrt(), ZERO);
+    }
+    #[test]
+    fn equals_tolerates_rounding_at_either_precision() {
+        let third: Complex<f32> = complex!(1.0, -2.0) / complex!(3.0, 0.0);
+        assert!((third * complex!(3.0, 0.0)).equals(complex!(1.0, -2.0)));
+        assert!(complex!(0.1_f32 + 0.2, 0.0).equals(complex!(0.3, 0.0)));
+        assert!(complex!(0.1 + 0.2, 0.0).equals(complex!(0.3, 0.0)));
+        assert!(!complex!(1.0_f32, 0.0).equals(complex!(1.0001, 0.0)));
+        assert!(!complex!(1.0, 0.0).equals(complex!(1.0 + 1e-12, 0.0)));
+    }
+    #[test]
+    fn non_finite_parts_are_detected() {
+        assert!(complex!(1.0, -2.0).is_finite());
+        assert!(!complex!(f64::NAN, 0.0).is_finite());
+        assert!(!complex!(0.0, f64::INFINITY).is_finite());
+        assert!(!(ONE / ZERO).is_finite());
+    }
+    #[test]
+    fn complex_display_trims_zeros_and_signs() {
+        assert_eq!(complex!(0.5, 0.5).to_string(), "0.5+0.5i");
+        assert_eq!(complex!(0.5, -0.25).to_string(), "0.5-0.25i");
+        assert_eq!(complex!(-1.0, 0.0).to_string(), "-1");
+        assert_eq!(complex!(0.0, -2.0).to_string(), "-2i");
+        assert_eq!(complex!(-0.0001, 0.0).to_string(), "0");
+        assert_eq!(format!("{:.1}", complex!(0.26, 1.0)), "0.3+1i");
     }
 }
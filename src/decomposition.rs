@@ -0,0 +1,617 @@
+use crate::circuit::{Circuit, Gate};
+use crate::complex;
+use crate::complex::Complex;
+use crate::constants::{IR2, ONE, X_GATE, Y_GATE, ZERO, Z_GATE};
+use crate::linalg::{real_determinant, symmetric_eigen};
+use crate::matrix::QMatrix;
+use crate::QuantumVec;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+const TOLERANCE: f64 = 1e-9;
+/// How many random combinations `kak_decomposition` tries before giving up.
+const MAX_COMBINATIONS: usize = 100;
+
+/// The rotation axes of an Euler decomposition, outermost axis first.
+/// `ZSX` expresses the middle Y rotation with two `Sx` gates, so that only
+/// `rz` and `sx` gates are emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EulerBasis {
+    ZYZ,
+    ZXZ,
+    XYX,
+    XZX,
+    ZSX,
+}
+/// A single-qubit unitary written as U = e^{i·phase}·R(phi)·R(theta)·R(lambda),
+/// where the rotation axes are given by the basis and R(lambda) is applied first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EulerAngles {
+    pub basis: EulerBasis,
+    pub theta: f64,
+    pub phi: f64,
+    pub lambda: f64,
+    pub phase: f64,
+}
+impl EulerAngles {
+    /// The gates of the decomposition in the order they are applied.
+    /// The global phase is not included.
+    pub fn gates(&self) -> Vec<Gate> {
+        let (theta, phi, lambda) = (self.theta, self.phi, self.lambda);
+        match self.basis {
+            EulerBasis::ZYZ => vec![Gate::Rz(lambda), Gate::Ry(theta), Gate::Rz(phi)],
+            EulerBasis::ZXZ => vec![Gate::Rz(lambda), Gate::Rx(theta), Gate::Rz(phi)],
+            EulerBasis::XYX => vec![Gate::Rx(lambda), Gate::Ry(theta), Gate::Rx(phi)],
+            EulerBasis::XZX => vec![Gate::Rx(lambda), Gate::Rz(theta), Gate::Rx(phi)],
+            EulerBasis::ZSX => vec![
+                Gate::Rz(lambda),
+                Gate::Sx,
+                Gate::Rz(theta - PI),
+                Gate::Sx,
+                Gate::Rz(phi + PI),
+            ],
+        }
+    }
+    /// Rebuilds the unitary from the angles, including the global phase.
+    pub fn matrix(&self) -> QMatrix {
+        let product = self
+            .gates()
+            .iter()
+            .fold(QMatrix::identity(2), |acc, gate| acc.mul(&gate.matrix()));
+        let (sin, cos) = self.phase.sin_cos();
+        complex!(cos, sin) * product
+    }
+}
+/// Decomposes a 2x2 unitary into Euler angles in the given basis.
+pub fn euler_angles(unitary: &QMatrix, basis: EulerBasis) -> EulerAngles {
+    assert_eq!(unitary.dims(), 2);
+    match basis {
+        EulerBasis::ZYZ => zyz_angles(unitary),
+        EulerBasis::ZXZ => {
+            // Rx(θ) = Rz(-π/2)·Ry(θ)·Rz(π/2)
+            let zyz = zyz_angles(unitary);
+            EulerAngles {
+                basis,
+                phi: zyz.phi + FRAC_PI_2,
+                lambda: zyz.lambda - FRAC_PI_2,
+                ..zyz
+            }
+        }
+        EulerBasis::XYX => {
+            // H·Rz(θ)·H = Rx(θ) and H·Ry(θ)·H = Ry(-θ)
+            let zyz = zyz_angles(&hadamard_conjugate(unitary));
+            EulerAngles {
+                basis,
+                theta: -zyz.theta,
+                ..zyz
+            }
+        }
+        EulerBasis::XZX => {
+            // H·Rz(θ)·H = Rx(θ) and H·Rx(θ)·H = Rz(θ)
+            let zxz = euler_angles(&hadamard_conjugate(unitary), EulerBasis::ZXZ);
+            EulerAngles { basis, ..zxz }
+        }
+        EulerBasis::ZSX => {
+            // Ry(θ) = Rz(π)·Rx(π/2)·Rz(θ - π)·Rx(π/2) and Rx(π/2) = e^{-iπ/4}·Sx
+            let zyz = zyz_angles(unitary);
+            EulerAngles {
+                basis,
+                phase: zyz.phase - FRAC_PI_2,
+                ..zyz
+            }
+        }
+    }
+}
+fn zyz_angles(unitary: &QMatrix) -> EulerAngles {
+    let [a, b, c, d]: [Complex; 4] = unitary.data_slice().try_into().unwrap();
+    // Dividing out the square root of the determinant leaves a matrix in SU(2),
+    // [[e^{-i(φ+λ)/2}·cos(θ/2), -e^{-i(φ-λ)/2}·sin(θ/2)], [e^{i(φ-λ)/2}·sin(θ/2), e^{i(φ+λ)/2}·cos(θ/2)]].
+    let det = a * d - b * c;
+    let phase = det.arg() / 2.0;
+    let (sin, cos) = (-phase).sin_cos();
+    let rotation = complex!(cos, sin);
+    let (c, d) = (c * rotation, d * rotation);
+
+    let theta = 2.0 * c.mag().atan2(d.mag());
+    let sum = 2.0 * d.arg();
+    let difference = 2.0 * c.arg();
+    EulerAngles {
+        basis: EulerBasis::ZYZ,
+        theta,
+        phi: (sum + difference) / 2.0,
+        lambda: (sum - difference) / 2.0,
+        phase,
+    }
+}
+/// Coordinates of the canonical gate exp(i(a·XX + b·YY + c·ZZ)) in the Weyl chamber
+/// π/4 ≥ a ≥ b ≥ |c|, with c ≥ 0 whenever a = π/4. Two-qubit gates are equal up to
+/// single-qubit gates exactly when their coordinates are equal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeylCoordinates {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+impl WeylCoordinates {
+    /// The canonical gate exp(i(a·XX + b·YY + c·ZZ)).
+    pub fn matrix(&self) -> QMatrix {
+        [(self.a, &*X_GATE), (self.b, &*Y_GATE), (self.c, &*Z_GATE)]
+            .into_iter()
+            .fold(QMatrix::identity(4), |acc, (angle, pauli)| {
+                let (sin, cos) = angle.sin_cos();
+                let exponential = QMatrix::from_data(
+                    pauli
+                        .kronecker(pauli)
+                        .data_slice()
+                        .iter()
+                        .zip(QMatrix::<f64>::identity(4).data_slice())
+                        .map(|(&p, &i)| cos * i + complex!(0.0, sin) * p)
+                        .collect(),
+                );
+                acc.mul(&exponential)
+            })
+    }
+    /// The number of CNOTs needed to implement a gate with these coordinates.
+    pub fn cnot_count(&self) -> usize {
+        let near = |x: f64, y: f64| (x - y).abs() < TOLERANCE;
+        match (self.a, self.b, self.c) {
+            (a, b, c) if near(a, 0.0) && near(b, 0.0) && near(c, 0.0) => 0,
+            (a, b, c) if near(a, FRAC_PI_4) && near(b, 0.0) && near(c, 0.0) => 1,
+            (_, _, c) if near(c, 0.0) => 2,
+            _ => 3,
+        }
+    }
+}
+/// A two-qubit unitary written as
+/// U = e^{i·phase}·(after[0] ⊗ after[1])·exp(i(a·XX + b·YY + c·ZZ))·(before[0] ⊗ before[1]),
+/// where index 0 of the single-qubit gates acts on the gate's most significant qubit.
+#[derive(Debug, Clone)]
+pub struct TwoQubitDecomposition {
+    pub coordinates: WeylCoordinates,
+    pub before: [QMatrix; 2],
+    pub after: [QMatrix; 2],
+    pub phase: f64,
+}
+impl TwoQubitDecomposition {
+    pub fn cnot_count(&self) -> usize {
+        self.coordinates.cnot_count()
+    }
+    /// Rebuilds the unitary, including the global phase.
+    pub fn matrix(&self) -> QMatrix {
+        let (sin, cos) = self.phase.sin_cos();
+        let before = self.before[1].kronecker(&self.before[0]);
+        let after = self.after[1].kronecker(&self.after[0]);
+        complex!(cos, sin) * before.mul(&self.coordinates.matrix()).mul(&after)
+    }
+    /// A two-qubit circuit with at most three CNOTs implementing the unitary up to
+    /// global phase. Qubit 1 is the gate's most significant qubit.
+    pub fn circuit(&self) -> Circuit {
+        let WeylCoordinates { a, b, c } = self.coordinates;
+        let mut circuit = Circuit::new(2);
+        circuit
+            .add(Gate::Unitary(self.before[0].clone()), &[1])
+            .add(Gate::Unitary(self.before[1].clone()), &[0]);
+        match self.cnot_count() {
+            0 => {}
+            // exp(iπ/4·XX) ≃ (H ⊗ I)·(Rz(-π/2) ⊗ Rx(-π/2))·CNOT·(H ⊗ I)
+            1 => {
+                circuit
+                    .h(1)
+                    .cnot(1, 0)
+                    .rz(-FRAC_PI_2, 1)
+                    .rx(-FRAC_PI_2, 0)
+                    .h(1);
+            }
+            // exp(i(a·XX + c·ZZ)) = CNOT·(Rx(-2a) ⊗ Rz(-2c))·CNOT, and conjugating
+            // by Rx(π/2) ⊗ Rx(π/2) exchanges YY and ZZ.
+            2 => {
+                circuit
+                    .rx(-FRAC_PI_2, 1)
+                    .rx(-FRAC_PI_2, 0)
+                    .cnot(1, 0)
+                    .rx(-2.0 * a, 1)
+                    .rz(-2.0 * b, 0)
+                    .cnot(1, 0)
+                    .rx(FRAC_PI_2, 1)
+                    .rx(FRAC_PI_2, 0);
+            }
+            // Vatan and Williams, Optimal quantum circuits for general two-qubit gates.
+            _ => {
+                circuit
+                    .rz(FRAC_PI_2, 1)
+                    .cnot(1, 0)
+                    .ry(FRAC_PI_2 - 2.0 * a, 1)
+                    .rz(FRAC_PI_2 - 2.0 * c, 0)
+                    .cnot(0, 1)
+                    .ry(2.0 * b - FRAC_PI_2, 1)
+                    .cnot(1, 0)
+                    .rz(-FRAC_PI_2, 0);
+            }
+        }
+        circuit
+            .add(Gate::Unitary(self.after[0].clone()), &[1])
+            .add(Gate::Unitary(self.after[1].clone()), &[0]);
+        circuit
+    }
+}
+pub fn weyl_coordinates(unitary: &QMatrix) -> WeylCoordinates {
+    kak_decomposition(unitary).coordinates
+}
+/// Decomposes a 4x4 unitary into single-qubit gates around a canonical gate.
+///
+/// In the magic (Bell) basis, single-qubit gates on both qubits become real orthogonal
+/// matrices and the canonical gate becomes diagonal. Writing the unitary in that basis
+/// as O1·D·O2, the orthogonal O2 diagonalizes the symmetric unitary UᵀU = O2ᵀ·D²·O2.
+/// Its real and imaginary parts commute, so they are diagonalized together through
+/// a random real combination of the two. Panics if the matrix is not unitary.
+pub fn kak_decomposition(unitary: &QMatrix) -> TwoQubitDecomposition {
+    assert_eq!(unitary.dims(), 4);
+    assert!(
+        unitary
+            .mul(&unitary.adjoint())
+            .approx_eq(&QMatrix::identity(4), 1e-9),
+        "the matrix must be unitary"
+    );
+    let magic = magic_basis();
+    let det_phase = unitary.determinant().arg() / 4.0;
+    let special = Complex::cis(-det_phase) * unitary.clone();
+    let magic_unitary = product(&[&magic.adjoint(), &special, &magic]);
+    let squared = product(&[&magic_unitary.transpose(), &magic_unitary]);
+
+    let re: Vec<f64> = squared.data_slice().iter().map(|z| z.re).collect();
+    let im: Vec<f64> = squared.data_slice().iter().map(|z| z.im).collect();
+    let mut rng = StdRng::seed_from_u64(4);
+    // A combination fails only if it makes distinct eigenvalues of D² coincide, which
+    // a random weight almost never does, so running out means the input is degenerate.
+    let mut diagonalization = None;
+    for _ in 0..MAX_COMBINATIONS {
+        let weight: f64 = rng.gen_range(0.5..2.0);
+        let combination: Vec<f64> = re.iter().zip(&im).map(|(r, i)| r + weight * i).collect();
+        let (_, vectors) = symmetric_eigen(&combination, 4);
+        let candidate = real_matrix(&vectors);
+        let diagonal = product(&[&candidate.transpose(), &squared, &candidate]);
+        let off_diagonal = (0..16)
+            .filter(|i| i / 4 != i % 4)
+            .all(|i| diagonal.data_slice()[i].mag() < TOLERANCE);
+        if off_diagonal {
+            diagonalization = Some((vectors, diagonal));
+            break;
+        }
+    }
+    let (mut orthogonal, diagonal) = diagonalization
+        .expect("UᵀU in the magic basis could not be diagonalized by a real rotation");
+    if real_determinant(&orthogonal, 4) < 0.0 {
+        for row in 0..4 {
+            orthogonal[row * 4] = -orthogonal[row * 4];
+        }
+    }
+    // Take square roots of D², choosing branches so that det(D) = 1.
+    let mut half_angles: Vec<f64> = (0..4)
+        .map(|k| diagonal.data_slice()[k * 4 + k].arg() / 2.0)
+        .collect();
+    if Complex::cis(half_angles.iter().sum::<f64>()).re < 0.0 {
+        half_angles[0] += PI;
+    }
+    let orthogonal = real_matrix(&orthogonal);
+    let inverse_root = diagonal_matrix(half_angles.iter().map(|&angle| Complex::cis(-angle)));
+    let left = product(&[&magic_unitary, &orthogonal, &inverse_root]);
+    let left = product(&[&magic, &left, &magic.adjoint()]);
+    let right = product(&[&magic, &orthogonal.transpose(), &magic.adjoint()]);
+
+    // The canonical gate is diagonal in the magic basis with entries
+    // e^{i(g + a·x_k + b·y_k + c·z_k)}, where x_k, y_k, z_k = ±1 and g is a phase.
+    let signs: Vec<Vec<f64>> = [&*X_GATE, &*Y_GATE, &*Z_GATE]
+        .iter()
+        .map(|pauli| {
+            let diagonal = product(&[&magic.adjoint(), &pauli.kronecker(pauli), &magic]);
+            (0..4)
+                .map(|k| diagonal.data_slice()[k * 4 + k].re)
+                .collect()
+        })
+        .collect();
+    let solve = |signs: &[f64]| -> f64 {
+        signs
+            .iter()
+            .zip(&half_angles)
+            .map(|(s, t)| s * t)
+            .sum::<f64>()
+            / 4.0
+    };
+    let mut canonical = Canonicalization {
+        coordinates: [solve(&signs[0]), solve(&signs[1]), solve(&signs[2])],
+        left,
+        right,
+        phase: det_phase + solve(&[1.0; 4]),
+    };
+    canonical.canonicalize();
+    let [a, b, c] = canonical.coordinates;
+    TwoQubitDecomposition {
+        coordinates: WeylCoordinates { a, b, c },
+        before: split_tensor(&canonical.right),
+        after: split_tensor(&canonical.left),
+        phase: canonical.phase,
+    }
+}
+/// Tracks U = e^{i·phase}·left·exp(i(a·XX + b·YY + c·ZZ))·right while moving the
+/// coordinates into the Weyl chamber using local equivalences.
+struct Canonicalization {
+    coordinates: [f64; 3],
+    left: QMatrix,
+    right: QMatrix,
+    phase: f64,
+}
+impl Canonicalization {
+    fn canonicalize(&mut self) {
+        for index in 0..3 {
+            let turns = (self.coordinates[index] / FRAC_PI_2).round() as i64;
+            self.shift(index, turns);
+        }
+        for _ in 0..2 {
+            for index in 0..2 {
+                if self.coordinates[index].abs() < self.coordinates[index + 1].abs() {
+                    self.swap(index);
+                }
+            }
+        }
+        if self.coordinates[0] < 0.0 {
+            self.flip(1);
+        }
+        if self.coordinates[1] < 0.0 {
+            self.flip(0);
+        }
+        if (self.coordinates[0] - FRAC_PI_4).abs() < TOLERANCE && self.coordinates[2] < 0.0 {
+            self.flip(1);
+            self.shift(0, -1);
+        }
+    }
+    /// Uses exp(iπ/2·P⊗P) = i·P⊗P to subtract turns·π/2 from a coordinate.
+    fn shift(&mut self, index: usize, turns: i64) {
+        self.coordinates[index] -= turns as f64 * FRAC_PI_2;
+        self.phase += turns as f64 * FRAC_PI_2;
+        if turns % 2 != 0 {
+            let pauli = pauli(index);
+            self.right = product(&[&pauli.kronecker(&pauli), &self.right]);
+        }
+    }
+    /// Conjugates by a Pauli on one qubit, which negates the two coordinates
+    /// other than `keep`.
+    fn flip(&mut self, keep: usize) {
+        let conjugation = QMatrix::identity(2).kronecker(&pauli(keep));
+        self.left = product(&[&self.left, &conjugation]);
+        self.right = product(&[&conjugation, &self.right]);
+        for index in (0..3).filter(|&index| index != keep) {
+            self.coordinates[index] = -self.coordinates[index];
+        }
+    }
+    /// Conjugates by a quarter turn on both qubits, which exchanges the coordinates
+    /// at `index` and `index + 1`.
+    fn swap(&mut self, index: usize) {
+        let rotation = match index {
+            // S maps X to Y and Y to -X.
+            0 => Gate::S.matrix(),
+            // Rx(π/2) maps Y to Z and Z to -Y.
+            _ => Gate::Rx(FRAC_PI_2).matrix(),
+        };
+        let conjugation = rotation.kronecker(&rotation);
+        self.left = product(&[&self.left, &conjugation.adjoint()]);
+        self.right = product(&[&conjugation, &self.right]);
+        self.coordinates.swap(index, index + 1);
+    }
+}
+fn pauli(index: usize) -> QMatrix {
+    [&*X_GATE, &*Y_GATE, &*Z_GATE][index].clone()
+}
+/// The columns are the Bell states (|00⟩ + |11⟩)/√2, i(|01⟩ + |10⟩)/√2,
+/// (|01⟩ - |10⟩)/√2 and i(|00⟩ - |11⟩)/√2.
+fn magic_basis() -> QMatrix {
+    let (r, i) = (complex!(IR2, 0.0), complex!(0.0, IR2));
+    #[rustfmt::skip]
+    let data = vec![
+        r, ZERO, ZERO, i,
+        ZERO, i, r, ZERO,
+        ZERO, i, -r, ZERO,
+        r, ZERO, ZERO, -i,
+    ];
+    QMatrix::from_data(data)
+}
+/// Splits a 4x4 matrix known to be a tensor product A ⊗ B into [A, B], with B in SU(2).
+fn split_tensor(matrix: &QMatrix) -> [QMatrix; 2] {
+    let data = matrix.data_slice();
+    let block = |i: usize, j: usize| -> Vec<Complex> {
+        vec![
+            data[(2 * i) * 4 + 2 * j],
+            data[(2 * i) * 4 + 2 * j + 1],
+            data[(2 * i + 1) * 4 + 2 * j],
+            data[(2 * i + 1) * 4 + 2 * j + 1],
+        ]
+    };
+    let norm = |block: &[Complex]| block.iter().map(|z| z.prob()).sum::<f64>();
+    let (i, j) = [(0, 0), (0, 1), (1, 0), (1, 1)]
+        .into_iter()
+        .max_by(|&(a, b), &(c, d)| norm(&block(a, b)).total_cmp(&norm(&block(c, d))))
+        .unwrap();
+    let reference = block(i, j);
+    let reference_norm = norm(&reference);
+    let outer: Vec<Complex> = [(0, 0), (0, 1), (1, 0), (1, 1)]
+        .into_iter()
+        .map(|(i, j)| {
+            reference
+                .iter()
+                .zip(block(i, j))
+                .map(|(&r, z)| r.conj() * z)
+                .sum::<Complex>()
+                / reference_norm
+        })
+        .collect();
+    let inner = QMatrix::from_data(reference);
+    let det = inner.determinant();
+    let root = Complex::cis(det.arg() / 2.0) * det.mag().sqrt();
+    [root * QMatrix::from_data(outer), ONE / root * inner]
+}
+/// The mathematical product of the matrices from left to right.
+fn product(matrices: &[&QMatrix]) -> QMatrix {
+    matrices
+        .iter()
+        .skip(1)
+        .fold(matrices[0].clone(), |acc, matrix| matrix.mul(&acc))
+}
+fn real_matrix(data: &[f64]) -> QMatrix {
+    QMatrix::from_data(data.iter().map(|&x| complex!(x, 0.0)).collect())
+}
+fn diagonal_matrix(entries: impl Iterator<Item = Complex>) -> QMatrix {
+    let entries: Vec<Complex> = entries.collect();
+    let dims = entries.len();
+    let mut data = vec![ZERO; dims * dims];
+    for (k, entry) in entries.into_iter().enumerate() {
+        data[k * dims + k] = entry;
+    }
+    QMatrix::from_data(data)
+}
+fn hadamard_conjugate(unitary: &QMatrix) -> QMatrix {
+    let hadamard = Gate::H.matrix();
+    hadamard.mul(unitary).mul(&hadamard)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BASES: [EulerBasis; 5] = [
+        EulerBasis::ZYZ,
+        EulerBasis::ZXZ,
+        EulerBasis::XYX,
+        EulerBasis::XZX,
+        EulerBasis::ZSX,
+    ];
+
+    fn random_unitary(rng: &mut impl Rng) -> QMatrix {
+        let euler = EulerAngles {
+            basis: EulerBasis::ZYZ,
+            theta: rng.gen_range(0.0..PI),
+            phi: rng.gen_range(-PI..PI),
+            lambda: rng.gen_range(-PI..PI),
+            phase: rng.gen_range(-PI..PI),
+        };
+        euler.matrix()
+    }
+    fn assert_coordinates(unitary: &QMatrix, a: f64, b: f64, c: f64) {
+        let coordinates = weyl_coordinates(unitary);
+        assert!((coordinates.a - a).abs() < 1e-9, "{coordinates:?}");
+        assert!((coordinates.b - b).abs() < 1e-9, "{coordinates:?}");
+        assert!((coordinates.c - c).abs() < 1e-9, "{coordinates:?}");
+    }
+    #[test]
+    fn weyl_coordinates_of_standard_gates_are_canonical() {
+        let iswap = QMatrix::from_data(vec![
+            ONE, ZERO, ZERO, ZERO, ZERO, ZERO, I, ZERO, ZERO, I, ZERO, ZERO, ZERO, ZERO, ZERO, ONE,
+        ]);
+        assert_coordinates(&QMatrix::identity(4), 0.0, 0.0, 0.0);
+        assert_coordinates(&H_GATE.kronecker(&T_GATE), 0.0, 0.0, 0.0);
+        assert_coordinates(&CNOT_GATE, FRAC_PI_4, 0.0, 0.0);
+        assert_coordinates(&CZ, FRAC_PI_4, 0.0, 0.0);
+        assert_coordinates(&iswap, FRAC_PI_4, FRAC_PI_4, 0.0);
+        assert_coordinates(&SWAP_GATE, FRAC_PI_4, FRAC_PI_4, FRAC_PI_4);
+    }
+    #[test]
+    fn kak_decomposition_of_standard_gates_uses_minimal_cnots() {
+        for (gate, cnots) in [(&*CNOT_GATE, 1), (&*CZ, 1), (&*SWAP_GATE, 3)] {
+            let decomposition = kak_decomposition(gate);
+            assert_eq!(decomposition.cnot_count(), cnots);
+            assert!(decomposition.matrix().approx_eq(gate, 1e-9));
+            assert!(decomposition
+                .circuit()
+                .unitary()
+                .equals_up_to_phase(gate, 1e-9));
+        }
+    }
+    #[test]
+    fn kak_decomposition_reconstructs_haar_random_unitaries() {
+        let mut rng = StdRng::seed_from_u64(29);
+        for _ in 0..50 {
+            let unitary = QMatrix::haar_random(4, &mut rng);
+            let decomposition = kak_decomposition(&unitary);
+            let WeylCoordinates { a, b, c } = decomposition.coordinates;
+            assert!(FRAC_PI_4 + 1e-9 >= a && a >= b && b >= c.abs());
+            assert!(decomposition.matrix().approx_eq(&unitary, 1e-9));
+            let circuit = decomposition.circuit();
+            assert!(circuit.unitary().equals_up_to_phase(&unitary, 1e-9));
+            let cnots = circuit
+                .instructions()
+                .iter()
+                .filter(|instruction| {
+                    matches!(
+                        instruction,
+                        crate::circuit::Instruction::Gate {
+                            gate: Gate::Cnot,
+                            ..
+                        }
+                    )
+                })
+                .count();
+            assert!(cnots <= 3);
+        }
+    }
+    #[test]
+    #[should_panic(expected = "must be unitary")]
+    fn kak_decomposition_rejects_non_unitary_matrices() {
+        kak_decomposition(&(2.0 * QMatrix::identity(4)));
+    }
+    #[test]
+    fn kak_decomposition_of_partial_swaps_uses_two_cnots() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let locals =
+            QMatrix::haar_random(2, &mut rng).kronecker(&QMatrix::haar_random(2, &mut rng));
+        let canonical = WeylCoordinates {
+            a: 0.6,
+            b: 0.2,
+            c: 0.0,
+        };
+        let unitary = locals.mul(&canonical.matrix());
+        let decomposition = kak_decomposition(&unitary);
+        assert_eq!(decomposition.cnot_count(), 2);
+        assert!((decomposition.coordinates.a - 0.6).abs() < 1e-9);
+        assert!(decomposition
+            .circuit()
+            .unitary()
+            .equals_up_to_phase(&unitary, 1e-9));
+    }
+    #[test]
+    fn decomposing_standard_gates_reconstructs_them_in_every_basis() {
+        let gates = [
+            &*I_GATE, &*X_GATE, &*Y_GATE, &*Z_GATE, &*H_GATE, &*S_GATE, &*T_GATE,
+        ];
+        for gate in gates {
+            for basis in BASES {
+                let angles = euler_angles(gate, basis);
+                assert!(angles.matrix().approx_eq(gate, 1e-10));
+            }
+        }
+    }
+    #[test]
+    fn decomposing_random_unitaries_reconstructs_them_in_every_basis() {
+        let mut rng = StdRng::seed_from_u64(28);
+        for _ in 0..100 {
+            let unitary = random_unitary(&mut rng);
+            for basis in BASES {
+                let angles = euler_angles(&unitary, basis);
+                assert!(angles.matrix().approx_eq(&unitary, 1e-10));
+            }
+        }
+    }
+    #[test]
+    fn zsx_basis_only_emits_rz_and_sx_gates() {
+        let angles = euler_angles(&H_GATE, EulerBasis::ZSX);
+        assert!(angles
+            .gates()
+            .iter()
+            .all(|gate| matches!(gate, Gate::Rz(_) | Gate::Sx)));
+    }
+    #[test]
+    fn hadamard_has_quarter_turn_y_rotation_in_zyz_basis() {
+        let angles = euler_angles(&H_GATE, EulerBasis::ZYZ);
+        assert!((angles.theta - FRAC_PI_2).abs() < 1e-12);
+    }
+}
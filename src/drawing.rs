@@ -0,0 +1,414 @@
+use crate::circuit::{Circuit, Gate, Instruction};
+use crate::complex::format_real;
+use std::fmt;
+
+/// The characters used to draw a circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Unicode,
+    Ascii,
+}
+struct Symbols {
+    wire: char,
+    classical_wire: char,
+    vertical: char,
+    classical_vertical: char,
+    crossing: char,
+    classical_crossing: char,
+    double_crossing: char,
+    control: char,
+    target: char,
+    swap: char,
+    measured: char,
+    barrier: char,
+    continuation_end: char,
+    continuation_start: char,
+}
+const UNICODE: Symbols = Symbols {
+    wire: '─',
+    classical_wire: '═',
+    vertical: '│',
+    classical_vertical: '║',
+    crossing: '┼',
+    classical_crossing: '╫',
+    double_crossing: '╬',
+    control: '■',
+    target: '⊕',
+    swap: '╳',
+    measured: '╩',
+    barrier: '░',
+    continuation_end: '»',
+    continuation_start: '«',
+};
+const ASCII: Symbols = Symbols {
+    wire: '-',
+    classical_wire: '=',
+    vertical: '|',
+    classical_vertical: '|',
+    crossing: '|',
+    classical_crossing: '|',
+    double_crossing: '|',
+    control: '*',
+    target: '+',
+    swap: 'x',
+    measured: 'v',
+    barrier: '#',
+    continuation_end: '>',
+    continuation_start: '<',
+};
+/// Draws a circuit as text, with one wire per qubit from qubit 0 at the top and one
+/// double wire per classical bit below them. Gates are packed into columns as early as
+/// their wires allow, and lines longer than the width are wrapped.
+///
+/// `Circuit` implements `Display` with the default settings.
+pub struct TextDrawing<'a> {
+    circuit: &'a Circuit,
+    width: usize,
+    charset: Charset,
+    precision: usize,
+}
+/// The contents of one column of the drawing, indexed by row. Qubit q is drawn on
+/// row 2q, classical bit k on row 2(num_qubits + k), and the odd rows are spacers.
+struct Column {
+    cells: Vec<Option<String>>,
+}
+impl<'a> TextDrawing<'a> {
+    pub fn new(circuit: &'a Circuit) -> TextDrawing<'a> {
+        TextDrawing {
+            circuit,
+            width: 80,
+            charset: Charset::Unicode,
+            precision: 2,
+        }
+    }
+    /// The maximum line width before the drawing wraps.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+    pub fn charset(mut self, charset: Charset) -> Self {
+        self.charset = charset;
+        self
+    }
+    /// The number of decimals shown for gate angles.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+    fn symbols(&self) -> &'static Symbols {
+        match self.charset {
+            Charset::Unicode => &UNICODE,
+            Charset::Ascii => &ASCII,
+        }
+    }
+    fn num_rows(&self) -> usize {
+        2 * (self.circuit.num_qubits() + self.circuit.num_clbits()) - 1
+    }
+    fn is_classical_row(&self, row: usize) -> bool {
+        row >= 2 * self.circuit.num_qubits()
+    }
+    fn label(&self, gate: &Gate) -> String {
+        let angle =
+            |name: &str, angle: &f64| format!("{name}({})", format_real(*angle, self.precision));
+        let dagger = match self.charset {
+            Charset::Unicode => "†",
+            Charset::Ascii => "dg",
+        };
+        match gate {
+            Gate::I => "I".to_string(),
+            Gate::X => "X".to_string(),
+            Gate::Y => "Y".to_string(),
+            Gate::Z => "Z".to_string(),
+            Gate::H => "H".to_string(),
+            Gate::S => "S".to_string(),
+            Gate::Sdg => format!("S{dagger}"),
+            Gate::T => "T".to_string(),
+            Gate::Tdg => format!("T{dagger}"),
+            Gate::Sx => "SX".to_string(),
+            Gate::Sxdg => format!("SX{dagger}"),
+            Gate::Rx(theta) => angle("RX", theta),
+            Gate::Ry(theta) => angle("RY", theta),
+            Gate::Rz(theta) => angle("RZ", theta),
+            Gate::Phase(lambda) => angle("P", lambda),
+            Gate::Cnot | Gate::Cz | Gate::Swap | Gate::Unitary(_) => "U".to_string(),
+        }
+    }
+    /// The rows a gate draws on, and the symbol drawn on each of them.
+    fn gate_cells(&self, gate: &Gate, qubits: &[usize]) -> Vec<(usize, String)> {
+        let symbols = self.symbols();
+        let symbol = |c: char| c.to_string();
+        match gate {
+            Gate::Cnot => vec![
+                (2 * qubits[0], symbol(symbols.control)),
+                (2 * qubits[1], symbol(symbols.target)),
+            ],
+            Gate::Cz => vec![
+                (2 * qubits[0], symbol(symbols.control)),
+                (2 * qubits[1], symbol(symbols.control)),
+            ],
+            Gate::Swap => vec![
+                (2 * qubits[0], symbol(symbols.swap)),
+                (2 * qubits[1], symbol(symbols.swap)),
+            ],
+            _ => qubits.iter().map(|&q| (2 * q, self.label(gate))).collect(),
+        }
+    }
+    /// The rows an instruction draws on, and the symbol drawn on each of them. A
+    /// conditional gate shows the bit of the value it expects on each bit of its register.
+    fn cells(&self, instruction: &Instruction) -> Vec<(usize, String)> {
+        let symbols = self.symbols();
+        let clbit_row = |clbit: usize| 2 * (self.circuit.num_qubits() + clbit);
+        match instruction {
+            Instruction::Gate { gate, qubits } => self.gate_cells(gate, qubits),
+            Instruction::Measure { qubit, clbit } => vec![
+                (2 * qubit, "M".to_string()),
+                (clbit_row(*clbit), symbols.measured.to_string()),
+            ],
+            Instruction::Conditional {
+                register,
+                value,
+                gate,
+                qubits,
+            } => {
+                let mut cells = self.gate_cells(gate, qubits);
+                cells.extend(
+                    register
+                        .bits()
+                        .enumerate()
+                        .map(|(i, clbit)| (clbit_row(clbit), ((value >> i) & 1).to_string())),
+                );
+                cells
+            }
+            Instruction::Barrier => (0..2 * self.circuit.num_qubits() - 1)
+                .map(|row| (row, symbols.barrier.to_string()))
+                .collect(),
+        }
+    }
+    fn columns(&self) -> Vec<Column> {
+        let symbols = self.symbols();
+        let mut columns: Vec<Column> = Vec::new();
+        let mut next_free = vec![0; self.num_rows()];
+        for instruction in self.circuit.instructions() {
+            let cells = self.cells(instruction);
+            let first = cells.iter().map(|(row, _)| *row).min().unwrap();
+            let last = cells.iter().map(|(row, _)| *row).max().unwrap();
+            let index = next_free[first..=last].iter().copied().max().unwrap();
+            next_free[first..=last].fill(index + 1);
+            if index == columns.len() {
+                columns.push(Column {
+                    cells: vec![None; self.num_rows()],
+                });
+            }
+            let column = &mut columns[index];
+            let classical = !instruction.clbits().is_empty();
+            for row in first..=last {
+                column.cells[row] = Some(
+                    match (row % 2 == 0, classical, self.is_classical_row(row)) {
+                        (false, false, _) => symbols.vertical,
+                        (false, true, _) => symbols.classical_vertical,
+                        (true, false, _) => symbols.crossing,
+                        (true, true, false) => symbols.classical_crossing,
+                        (true, true, true) => symbols.double_crossing,
+                    }
+                    .to_string(),
+                );
+            }
+            for (row, cell) in cells {
+                column.cells[row] = Some(cell);
+            }
+        }
+        columns
+    }
+    fn row_label(&self, row: usize) -> String {
+        match (row % 2 == 0, self.is_classical_row(row)) {
+            (false, _) => String::new(),
+            (true, false) => format!("q{}: ", row / 2),
+            (true, true) => format!("c{}: ", row / 2 - self.circuit.num_qubits()),
+        }
+    }
+    fn fill(&self, row: usize) -> char {
+        match (row % 2 == 0, self.is_classical_row(row)) {
+            (false, _) => ' ',
+            (true, false) => self.symbols().wire,
+            (true, true) => self.symbols().classical_wire,
+        }
+    }
+}
+impl fmt::Display for TextDrawing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.circuit.num_qubits() == 0 {
+            return Ok(());
+        }
+        let symbols = self.symbols();
+        let columns = self.columns();
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|column| {
+                column
+                    .cells
+                    .iter()
+                    .flatten()
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(1)
+                    + 2
+            })
+            .collect();
+        let label_width = (0..self.num_rows())
+            .map(|row| self.row_label(row).chars().count())
+            .max()
+            .unwrap();
+
+        // Split the columns into pages that fit the width, leaving room for the
+        // labels, a trailing wire and the continuation markers.
+        let mut pages: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut start = 0;
+        let mut used = 0;
+        for (index, width) in widths.iter().enumerate() {
+            if index > start && label_width + 1 + used + width + 2 > self.width {
+                pages.push(start..index);
+                start = index;
+                used = 0;
+            }
+            used += width;
+        }
+        pages.push(start..columns.len());
+
+        for (page_index, page) in pages.iter().enumerate() {
+            if page_index > 0 {
+                writeln!(f)?;
+            }
+            let first_page = page_index == 0;
+            let last_page = page_index == pages.len() - 1;
+            for row in 0..self.num_rows() {
+                let fill = self.fill(row);
+                let mut line = format!("{:<label_width$}", self.row_label(row));
+                match (first_page, row % 2 == 0) {
+                    (true, _) | (false, false) => line.push(fill),
+                    (false, true) => line.push(symbols.continuation_start),
+                }
+                for index in page.clone() {
+                    let width = widths[index];
+                    match &columns[index].cells[row] {
+                        Some(cell) => {
+                            let length = cell.chars().count();
+                            let left = (width - length) / 2;
+                            line.extend(std::iter::repeat(fill).take(left));
+                            line.push_str(cell);
+                            line.extend(std::iter::repeat(fill).take(width - length - left));
+                        }
+                        None => line.extend(std::iter::repeat(fill).take(width)),
+                    }
+                }
+                if !last_page && row % 2 == 0 {
+                    line.push(symbols.continuation_end);
+                }
+                writeln!(f, "{}", line.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", TextDrawing::new(self))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixed_circuit() -> Circuit {
+        let mut circuit = Circuit::new(3);
+        circuit
+            .rz(0.5, 0)
+            .cnot(2, 0)
+            .h(1)
+            .barrier()
+            .swap(0, 2)
+            .add(Gate::Tdg, &[1])
+            .ry(1.23456, 2)
+            .cz(0, 1);
+        circuit
+    }
+    #[test]
+    fn drawing_bell_circuit_with_measurements() {
+        let mut circuit = Circuit::with_clbits(2, 2);
+        circuit.h(0).cnot(0, 1).measure(0, 0).measure(1, 1);
+        let expected = "\
+q0: ──H──■──M────
+         │  ║
+q1: ─────⊕──╫──M─
+            ║  ║
+c0: ════════╩══╬═
+               ║
+c1: ═══════════╩═
+";
+        assert_eq!(circuit.to_string(), expected);
+    }
+    #[test]
+    fn drawing_conditional_gate_shows_expected_bits() {
+        let mut circuit = Circuit::new(2);
+        let register = circuit.add_register(2);
+        circuit
+            .measure(0, 0)
+            .measure(1, 1)
+            .add_if(register, 2, Gate::X, &[0]);
+        let expected = "\
+q0: ──M─────X─
+      ║     ║
+q1: ──╫──M──╫─
+      ║  ║  ║
+c0: ══╩══╬══0═
+         ║  ║
+c1: ═════╩══1═
+";
+        assert_eq!(circuit.to_string(), expected);
+    }
+    #[test]
+    fn drawing_crosses_wires_between_distant_qubits() {
+        let expected = "\
+q0: ──RZ(0.5)──⊕─────░──╳────────────■─
+               │     ░  │            │
+q1: ───────────┼──H──░──┼─────T†─────■─
+               │     ░  │
+q2: ───────────■─────░──╳──RY(1.23)────
+";
+        assert_eq!(mixed_circuit().to_string(), expected);
+    }
+    #[test]
+    fn ascii_drawing_uses_given_precision() {
+        let circuit = mixed_circuit();
+        let drawing = TextDrawing::new(&circuit)
+            .charset(Charset::Ascii)
+            .precision(3);
+        let expected = "\
+q0: --RZ(0.5)--+-----#--x-------------*-
+               |     #  |             |
+q1: -----------|--H--#--|-----Tdg-----*-
+               |     #  |
+q2: -----------*-----#--x--RY(1.235)----
+";
+        assert_eq!(drawing.to_string(), expected);
+    }
+    #[test]
+    fn long_drawing_wraps_to_width() {
+        let mut circuit = Circuit::new(2);
+        for i in 0..4 {
+            circuit.rx(0.25 * i as f64, i % 2).cnot(i % 2, 1 - i % 2);
+        }
+        let drawing = TextDrawing::new(&circuit).width(40);
+        let expected = "\
+q0: ──RX(0)──■────────────⊕──RX(0.5)─»
+             │            │
+q1: ─────────⊕──RX(0.25)──■──────────»
+
+q0: «─■────────────⊕─
+      │            │
+q1: «─⊕──RX(0.75)──■─
+";
+        let output = drawing.to_string();
+        assert!(output.lines().all(|line| line.chars().count() <= 40));
+        assert_eq!(output, expected);
+    }
+}
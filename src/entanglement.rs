@@ -0,0 +1,163 @@
+//! Entanglement measures of pure states, split into the given qubits and the rest.
+use crate::complex::Complex;
+use crate::linalg::complex_svd;
+use crate::matrix::QMatrix;
+use crate::{gate_offsets, qubit_mask, QState};
+
+impl QState {
+    /// The density matrix of the given qubits after tracing out the others, with the
+    /// same qubit order as `apply_on`: `qubits[0]` is its most significant qubit.
+    pub fn reduced_density_matrix(&self, qubits: &[usize]) -> QMatrix {
+        let mask = qubit_mask(qubits, self.num_qubits());
+        let offsets = gate_offsets(qubits);
+        let dims = offsets.len();
+        let mut rho = vec![Complex::zero(); dims * dims];
+        for base in (0..self.state.len()).filter(|i| i & mask == 0) {
+            for (row, &row_offset) in offsets.iter().enumerate() {
+                let amplitude = self.state[base | row_offset];
+                for (col, &col_offset) in offsets.iter().enumerate() {
+                    rho[row * dims + col] += amplitude * self.state[base | col_offset].conj();
+                }
+            }
+        }
+        QMatrix::from_data(rho)
+    }
+    /// The Schmidt coefficients of the split into `qubits` and the other qubits, in
+    /// descending order. Their squares are the eigenvalues of either reduced state.
+    pub fn schmidt_coefficients(&self, qubits: &[usize]) -> Vec<f64> {
+        let mask = qubit_mask(qubits, self.num_qubits());
+        let rest: Vec<usize> = (0..self.num_qubits())
+            .filter(|q| mask >> q & 1 == 0)
+            .collect();
+        // The smaller side indexes the columns, which bounds the number of rotations.
+        let (rows, cols) = match qubits.len() <= rest.len() {
+            true => (gate_offsets(&rest), gate_offsets(qubits)),
+            false => (gate_offsets(qubits), gate_offsets(&rest)),
+        };
+        let coefficients: Vec<Complex> = rows
+            .iter()
+            .flat_map(|&row| cols.iter().map(move |&col| row | col))
+            .map(|index| self.state[index])
+            .collect();
+        complex_svd(&coefficients, rows.len(), cols.len()).1
+    }
+    /// The von Neumann entropy in bits of the reduced state of `qubits`, which is the
+    /// same for both sides of the split.
+    pub fn entanglement_entropy(&self, qubits: &[usize]) -> f64 {
+        self.schmidt_coefficients(qubits)
+            .iter()
+            .map(|s| s * s)
+            .filter(|&p| p > 0.0)
+            .map(|p| -p * p.log2())
+            .sum()
+    }
+    /// The Rényi entropy of order `alpha` in bits of the reduced state of `qubits`.
+    /// Order 1 is the von Neumann entropy.
+    pub fn renyi_entropy(&self, qubits: &[usize], alpha: f64) -> f64 {
+        assert!(alpha >= 0.0);
+        if (alpha - 1.0).abs() < 1e-12 {
+            return self.entanglement_entropy(qubits);
+        }
+        let sum: f64 = self
+            .schmidt_coefficients(qubits)
+            .iter()
+            .map(|s| s * s)
+            .filter(|&p| p > 0.0)
+            .map(|p| p.powf(alpha))
+            .sum();
+        sum.log2() / (1.0 - alpha)
+    }
+    /// The concurrence of a two-qubit state, |⟨ψ|Y⊗Y|ψ*⟩| = 2|ψ₀₀ψ₁₁ − ψ₀₁ψ₁₀|, which
+    /// is 0 for product states and 1 for maximally entangled ones.
+    pub fn concurrence(&self) -> f64 {
+        assert_eq!(self.num_qubits(), 2);
+        let psi = &self.state;
+        2.0 * (psi[0] * psi[3] - psi[1] * psi[2]).mag()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complex;
+    use crate::constants::*;
+    use crate::QuantumVec;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn bell_state() -> QState {
+        QState::basis_state(2, 0)
+            .apply_on(&H_GATE, &[0])
+            .apply_on(&CNOT_GATE, &[0, 1])
+    }
+    fn ghz_state() -> QState {
+        bell_state()
+            .state_tensor(&QState::basis_state(1, 0))
+            .apply_on(&CNOT_GATE, &[1, 2])
+    }
+    #[test]
+    fn bell_state_is_maximally_entangled() {
+        let bell = bell_state();
+        let rho = bell.reduced_density_matrix(&[1]);
+        assert!(rho.approx_eq(&(0.5 * I_GATE.clone()), 1e-12));
+        for (s, expected) in bell.schmidt_coefficients(&[0]).iter().zip([IR2, IR2]) {
+            assert!((s - expected).abs() < 1e-12);
+        }
+        assert!((bell.entanglement_entropy(&[0]) - 1.0).abs() < 1e-12);
+        assert!((bell.renyi_entropy(&[0], 2.0) - 1.0).abs() < 1e-12);
+        assert!((bell.concurrence() - 1.0).abs() < 1e-12);
+    }
+    #[test]
+    fn ghz_state_has_one_bit_across_every_cut() {
+        let ghz = ghz_state();
+        for qubits in [vec![0], vec![2], vec![0, 1], vec![2, 0]] {
+            assert!((ghz.entanglement_entropy(&qubits) - 1.0).abs() < 1e-12);
+            assert!((ghz.renyi_entropy(&qubits, 0.5) - 1.0).abs() < 1e-12);
+        }
+        let mut expected = vec![ZERO; 16];
+        expected[0] = complex!(0.5, 0.0);
+        expected[15] = complex!(0.5, 0.0);
+        let rho = ghz.reduced_density_matrix(&[2, 0]);
+        assert!(rho.approx_eq(&QMatrix::from_data(expected), 1e-12));
+    }
+    #[test]
+    fn product_state_is_not_entangled() {
+        let state = QState::basis_state(2, 0)
+            .apply_on(&H_GATE, &[0])
+            .apply_on(&T_GATE, &[0])
+            .apply_on(&H_GATE, &[1]);
+        assert!(state.entanglement_entropy(&[1]).abs() < 1e-12);
+        assert!(state.renyi_entropy(&[1], 3.0).abs() < 1e-12);
+        assert!(state.concurrence() < 1e-12);
+    }
+    #[test]
+    fn partial_entanglement_matches_closed_form() {
+        let theta: f64 = 0.3;
+        let (sin, cos) = theta.sin_cos();
+        let state = QState::from_data(vec![complex!(cos, 0.0), ZERO, ZERO, complex!(sin, 0.0)]);
+        let (p, q) = (cos * cos, sin * sin);
+        let entropy = -p * p.log2() - q * q.log2();
+        assert!((state.entanglement_entropy(&[0]) - entropy).abs() < 1e-12);
+        assert!((state.concurrence() - (2.0 * theta).sin()).abs() < 1e-12);
+        let renyi = -(p * p + q * q).log2();
+        assert!((state.renyi_entropy(&[1], 2.0) - renyi).abs() < 1e-12);
+    }
+    #[test]
+    fn schmidt_coefficients_match_reduced_spectrum() {
+        let mut rng = StdRng::seed_from_u64(45);
+        let amplitudes: Vec<Complex> = (0..32)
+            .map(|_| complex!(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+            .collect();
+        let norm = amplitudes.iter().map(|z| z.prob()).sum::<f64>().sqrt();
+        let state = QState::from_data(amplitudes.iter().map(|&z| z / norm).collect());
+        let qubits = [4, 1];
+        let rho = state.reduced_density_matrix(&qubits);
+        let trace: Complex = (0..4).map(|i| rho.data_slice()[5 * i]).sum();
+        assert!((trace - ONE).mag() < 1e-12);
+        let (values, _) = crate::linalg::hermitian_eigen(&rho);
+        let coefficients = state.schmidt_coefficients(&qubits);
+        for (value, s) in values.iter().rev().zip(&coefficients) {
+            assert!((value - s * s).abs() < 1e-12);
+        }
+        let total: f64 = coefficients.iter().map(|s| s * s).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,159 @@
+//! Gradients of expectation values with respect to the angles of a circuit's
+//! parameterized gates, in the order of `Circuit::parameters`.
+use crate::circuit::{Circuit, Gate, Instruction};
+use crate::complex::Complex;
+use crate::constants::{I, ZERO};
+use crate::matrix::QMatrix;
+use crate::pauli::PauliSum;
+use crate::{QState, QuantumVec};
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/// The gradient by the parameter-shift rule. Every parameterized gate is generated by
+/// a Pauli operator with eigenvalues ±1/2 up to a global phase, so
+/// ∂E/∂θ = (E(θ + π/2) − E(θ − π/2)) / 2 exactly. Only `expectation` is evaluated, two
+/// times per parameter, so it may estimate the value from measurement samples.
+pub fn parameter_shift_gradient(
+    circuit: &Circuit,
+    mut expectation: impl FnMut(&Circuit) -> f64,
+) -> Vec<f64> {
+    let parameters = circuit.parameters();
+    (0..parameters.len())
+        .map(|k| {
+            let mut shifted = parameters.clone();
+            shifted[k] = parameters[k] + FRAC_PI_2;
+            let plus = expectation(&circuit.with_parameters(&shifted));
+            shifted[k] = parameters[k] - FRAC_PI_2;
+            let minus = expectation(&circuit.with_parameters(&shifted));
+            (plus - minus) / 2.0
+        })
+        .collect()
+}
+/// The gradient of ⟨ψ|H|ψ⟩, where |ψ⟩ is the circuit applied to `initial`, by adjoint
+/// differentiation. One forward pass prepares |ψ⟩, and one backward pass undoes the
+/// gates on both |ψ⟩ and H|ψ⟩, picking up 2·Re⟨λ|∂U|φ⟩ at every parameterized gate,
+/// where |φ⟩ is the state before the gate and ⟨λ| the observable pulled back to after it.
+pub fn adjoint_gradient(circuit: &Circuit, observable: &PauliSum, initial: &QState) -> Vec<f64> {
+    let gates: Vec<(&Gate, &[usize])> = circuit
+        .instructions()
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Gate { gate, qubits } => Some((gate, qubits.as_slice())),
+            Instruction::Barrier => None,
+            _ => panic!("adjoint differentiation needs a circuit without measurements"),
+        })
+        .collect();
+    let mut phi = circuit.run(initial);
+    let mut lambda = observable.apply(&phi);
+    let mut gradient = Vec::new();
+    for (gate, qubits) in gates.into_iter().rev() {
+        phi = phi.apply_on(&gate.inverse().matrix(), qubits);
+        if let Some(derivative) = derivative(gate) {
+            let mu = phi.apply_on(&derivative, qubits);
+            gradient.push(2.0 * inner(&lambda, &mu).re);
+        }
+        lambda = lambda.apply_on(&gate.inverse().matrix(), qubits);
+    }
+    gradient.reverse();
+    gradient
+}
+/// The derivative of a parameterized gate's matrix with respect to its angle.
+fn derivative(gate: &Gate) -> Option<QMatrix> {
+    match *gate {
+        // R(θ) = e^{-iθP/2}, so R'(θ) = -iP/2·R(θ) = R(θ + π)/2.
+        Gate::Rx(_) | Gate::Ry(_) | Gate::Rz(_) => {
+            let angle = gate.parameter()? + PI;
+            Some(0.5 * gate.with_parameter(angle).matrix())
+        }
+        Gate::Phase(lambda) => Some(QMatrix::from_data(vec![
+            ZERO,
+            ZERO,
+            ZERO,
+            I * Complex::cis(lambda),
+        ])),
+        _ => None,
+    }
+}
+/// The inner product ⟨a|b⟩.
+fn inner(a: &QState, b: &QState) -> Complex {
+    a.data_slice()
+        .iter()
+        .zip(b.data_slice())
+        .map(|(x, y)| x.conj() * *y)
+        .sum()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pauli::Pauli;
+
+    fn ansatz() -> Circuit {
+        let mut circuit = Circuit::new(3);
+        circuit
+            .ry(0.3, 0)
+            .rx(-1.1, 1)
+            .h(2)
+            .cnot(0, 1)
+            .rz(0.8, 1)
+            .add(Gate::Phase(0.4), &[2])
+            .cnot(1, 2)
+            .ry(2.1, 2)
+            .barrier()
+            .rx(0.6, 0)
+            .cz(0, 2)
+            .rz(-0.5, 0);
+        circuit
+    }
+    fn observable() -> PauliSum {
+        let mut observable = PauliSum::new(3);
+        observable
+            .add_term(0.7, &[(0, Pauli::Z), (1, Pauli::Z)])
+            .add_term(-0.4, &[(2, Pauli::X)])
+            .add_term(0.3, &[(0, Pauli::Y), (2, Pauli::Z)]);
+        observable
+    }
+    fn finite_difference_gradient(circuit: &Circuit, observable: &PauliSum) -> Vec<f64> {
+        let initial = QState::basis_state(3, 0);
+        let energy = |parameters: &[f64]| {
+            observable.expectation(&circuit.with_parameters(parameters).run(&initial))
+        };
+        let parameters = circuit.parameters();
+        let step = 1e-6;
+        (0..parameters.len())
+            .map(|k| {
+                let (mut plus, mut minus) = (parameters.clone(), parameters.clone());
+                plus[k] += step;
+                minus[k] -= step;
+                (energy(&plus) - energy(&minus)) / (2.0 * step)
+            })
+            .collect()
+    }
+    #[test]
+    fn exact_gradients_match_finite_differences() {
+        let (circuit, observable) = (ansatz(), observable());
+        let initial = QState::basis_state(3, 0);
+        let expected = finite_difference_gradient(&circuit, &observable);
+        assert_eq!(expected.len(), 7);
+        let shift = parameter_shift_gradient(&circuit, |circuit| {
+            observable.expectation(&circuit.run(&initial))
+        });
+        let adjoint = adjoint_gradient(&circuit, &observable, &initial);
+        for ((expected, shift), adjoint) in expected.iter().zip(&shift).zip(&adjoint) {
+            assert!((shift - expected).abs() < 1e-7, "{shift} != {expected}");
+            assert!((adjoint - expected).abs() < 1e-7, "{adjoint} != {expected}");
+        }
+    }
+    #[test]
+    fn parameter_shift_works_with_sampled_expectations() {
+        // ⟨Z⟩ = cos θ after RY(θ), so the derivative is −sin θ.
+        let theta = 1.0;
+        let mut circuit = Circuit::new(1);
+        circuit.ry(theta, 0);
+        let shots = 4000;
+        let gradient = parameter_shift_gradient(&circuit, |circuit| {
+            let state = circuit.run(&QState::basis_state(1, 0));
+            let ones: usize = (0..shots).map(|_| state.clone().measure(0) as usize).sum();
+            1.0 - 2.0 * ones as f64 / shots as f64
+        });
+        assert!((gradient[0] + theta.sin()).abs() < 0.1);
+    }
+}
@@ -1,17 +1,44 @@
+pub mod algorithms;
+pub mod checkpoint;
+pub mod circuit;
 pub mod complex;
 pub mod constants;
+pub mod decomposition;
+pub mod drawing;
+mod entanglement;
+pub mod gradient;
+mod linalg;
+#[cfg(feature = "memmap2")]
+pub mod mapped;
 pub mod matrix;
-use std::ops::Mul;
+pub mod minimize;
+pub mod optimizer;
+pub mod pauli;
+pub mod povm;
+pub mod qaoa;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod sparse;
+pub mod transpiler;
+pub mod vqe;
+use std::{
+    fmt,
+    ops::{Index, Mul},
+};
 
-use complex::Complex;
+use complex::{format_real, Complex, Real, DEFAULT_PRECISION};
 use constants::{ONE, ZERO};
 use float_cmp::approx_eq;
 use matrix::QMatrix;
 use rand::{thread_rng, Rng};
-trait QuantumVec {
-    fn data_slice(&self) -> &[Complex];
-    fn equals(&self, other: &impl QuantumVec) -> bool {
-        self.data_slice().len() == self.data_slice().len()
+
+/// How far measurement probabilities may sum from one, which leaves room for the
+/// rounding of long single-precision simulations.
+const PROBABILITY_TOLERANCE: f64 = 1e-5;
+pub trait QuantumVec<T: Real = f64> {
+    fn data_slice(&self) -> &[Complex<T>];
+    fn equals(&self, other: &impl QuantumVec<T>) -> bool {
+        self.data_slice().len() == other.data_slice().len()
             && self
                 .data_slice()
                 .iter()
@@ -20,21 +47,73 @@ trait QuantumVec {
                 .all(|(a, b)| a.equals(*b))
     }
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClassicalBit {
     Off = 0,
     On = 1,
 }
 impl ClassicalBit {
-    // Maybe this is how the universe does it?
+    /// Draws an outcome with the probabilities of 0 and 1. They are renormalized
+    /// before sampling, but must sum to one within `PROBABILITY_TOLERANCE`.
     pub fn from_probs(prob_0: f64, prob_1: f64) -> ClassicalBit {
-        let mut rng = thread_rng();
-        match rng.gen_bool(prob_1) {
-            true => ClassicalBit::On,
-            false => ClassicalBit::Off
+        match sample_outcome(&[prob_0, prob_1]) {
+            0 => ClassicalBit::Off,
+            _ => ClassicalBit::On,
         }
     }
 }
+/// The outcomes of measuring several qubits, where bit i is the outcome for the i-th
+/// measured qubit. It displays with the last bit first, so the outcome of measuring
+/// every qubit reads as the basis index in binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitstring(Vec<ClassicalBit>);
+impl Bitstring {
+    pub fn from_bits(bits: Vec<ClassicalBit>) -> Bitstring {
+        Bitstring(bits)
+    }
+    /// The `len` lowest bits of `value`.
+    pub fn from_value(value: usize, len: usize) -> Bitstring {
+        Bitstring(
+            (0..len)
+                .map(|i| match value >> i & 1 {
+                    0 => ClassicalBit::Off,
+                    _ => ClassicalBit::On,
+                })
+                .collect(),
+        )
+    }
+    pub fn bits(&self) -> &[ClassicalBit] {
+        &self.0
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    /// The bits read as an unsigned integer with bit 0 least significant.
+    pub fn value(&self) -> usize {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, &bit)| (bit as usize) << i)
+            .sum()
+    }
+}
+impl Index<usize> for Bitstring {
+    type Output = ClassicalBit;
+    fn index(&self, index: usize) -> &ClassicalBit {
+        &self.0[index]
+    }
+}
+impl fmt::Display for Bitstring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .iter()
+            .rev()
+            .try_for_each(|&bit| write!(f, "{}", bit as usize))
+    }
+}
 #[derive(Debug, Clone, Copy)]
 pub struct Qubit {
     alpha: Complex,
@@ -52,9 +131,27 @@ impl Qubit {
             ClassicalBit::On => Qubit::new(ZERO, ONE),
         }
     }
+    /// Creates the qubit at polar angle `theta` and azimuth `phi` on the Bloch sphere,
+    /// cos(θ/2)|0⟩ + e^(iφ) sin(θ/2)|1⟩.
+    pub fn from_bloch(theta: f64, phi: f64) -> Qubit {
+        let (sin, cos) = (theta / 2.0).sin_cos();
+        Qubit::new(
+            complex!(cos, 0.0),
+            complex!(phi.cos() * sin, phi.sin() * sin),
+        )
+    }
     pub fn is_normalized(self) -> bool {
         approx_eq!(f64, self.alpha.mag().powi(2) + self.beta.mag().powi(2), 1.0)
     }
+    /// The (x, y, z) coordinates of the qubit on the Bloch sphere.
+    pub fn bloch_vector(self) -> (f64, f64, f64) {
+        let coherence = self.alpha.conj() * self.beta;
+        (
+            2.0 * coherence.re,
+            2.0 * coherence.im,
+            self.alpha.prob() - self.beta.prob(),
+        )
+    }
 }
 impl From<Qubit> for QState {
     fn from(value: Qubit) -> Self {
@@ -63,26 +160,79 @@ impl From<Qubit> for QState {
         }
     }
 }
+/// A state vector of `f64` amplitudes by default, or `f32` amplitudes for single precision.
 #[derive(Debug, Clone)]
-pub struct QState {
-    state: Vec<Complex>,
+pub struct QState<T = f64> {
+    state: Vec<Complex<T>>,
 }
-impl Mul<Complex> for QState {
-    type Output = QState;
-    fn mul(self, rhs: Complex) -> Self::Output {
-        let new_state: Vec<Complex> = self.state.into_iter().map(|z| z * rhs).collect();
+impl<T: Real> Mul<Complex<T>> for QState<T> {
+    type Output = QState<T>;
+    fn mul(self, rhs: Complex<T>) -> Self::Output {
+        let new_state: Vec<Complex<T>> = self.state.into_iter().map(|z| z * rhs).collect();
         QState { state: new_state }
     }
 }
-impl QuantumVec for QState {
-    fn data_slice(&self) -> &[Complex] {
+/// Prints the state in Dirac notation, such as `0.707|00⟩ + 0.707|11⟩`, leaving out
+/// amplitudes that round to zero. The precision sets the number of decimals, three by
+/// default, and the alternate flag `{:#}` prints amplitudes in polar form `r∠θ`.
+impl<T: Real> fmt::Display for QState<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(DEFAULT_PRECISION);
+        let num_qubits = self.num_qubits();
+        let terms: Vec<(bool, String)> = self
+            .state
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &amplitude)| {
+                let (negative, coefficient) =
+                    dirac_coefficient(amplitude.cast(), precision, f.alternate())?;
+                Some((negative, format!("{coefficient}|{index:0num_qubits$b}⟩")))
+            })
+            .collect();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (position, (negative, term)) in terms.iter().enumerate() {
+            match (position, negative) {
+                (0, true) => write!(f, "-{term}")?,
+                (0, false) => write!(f, "{term}")?,
+                (_, true) => write!(f, " - {term}")?,
+                (_, false) => write!(f, " + {term}")?,
+            }
+        }
+        Ok(())
+    }
+}
+/// Splits an amplitude into a sign and a coefficient for a sum of kets, or returns `None`
+/// if it rounds to zero.
+fn dirac_coefficient(amplitude: Complex, precision: usize, polar: bool) -> Option<(bool, String)> {
+    if polar {
+        let magnitude = format_real(amplitude.mag(), precision);
+        let phase = format_real(amplitude.arg(), precision);
+        return match (magnitude.as_str(), phase.as_str()) {
+            ("0", _) => None,
+            (_, "0") => Some((false, magnitude)),
+            _ => Some((false, format!("{magnitude}∠{phase}"))),
+        };
+    }
+    let re = format_real(amplitude.re, precision);
+    let im = format_real(amplitude.im, precision);
+    match (re.as_str(), im.as_str()) {
+        ("0", "0") => None,
+        (_, "0") => Some((re.starts_with('-'), re.trim_start_matches('-').to_string())),
+        ("0", _) => Some((
+            im.starts_with('-'),
+            format!("{}i", im.trim_start_matches('-')),
+        )),
+        _ => Some((false, format!("({:.*})", precision, amplitude))),
+    }
+}
+impl<T: Real> QuantumVec<T> for QState<T> {
+    fn data_slice(&self) -> &[Complex<T>] {
         &self.state
     }
 }
 impl QState {
-    pub fn from_data(state: Vec<Complex>) -> Self {
-        QState { state }
-    }
     pub fn from_qubits(qubits: &[Qubit]) -> Self {
         assert!(!qubits.is_empty());
         qubits
@@ -92,9 +242,27 @@ impl QState {
             .reduce(|acc: QState, e| acc.state_tensor(&e))
             .unwrap()
     }
+}
+impl<T: Real> QState<T> {
+    pub fn from_data(state: Vec<Complex<T>>) -> Self {
+        QState { state }
+    }
+    /// Creates the computational basis state |index⟩ on `num_qubits` qubits.
+    pub fn basis_state(num_qubits: usize, index: usize) -> Self {
+        assert!(index < 1 << num_qubits);
+        let mut state = vec![Complex::zero(); 1 << num_qubits];
+        state[index] = Complex::one();
+        QState { state }
+    }
+    /// Converts the amplitudes to another precision.
+    pub fn cast<U: Real>(&self) -> QState<U> {
+        QState {
+            state: self.state.iter().map(|z| z.cast()).collect(),
+        }
+    }
     /// Calculates the tensor product between two quantum states as
     /// other ⊗ self.
-    pub fn state_tensor(&self, lhs: &QState) -> Self {
+    pub fn state_tensor(&self, lhs: &QState<T>) -> Self {
         QState {
             state: lhs
                 .state
@@ -103,10 +271,9 @@ impl QState {
                 .collect(),
         }
     }
-    pub fn apply(&self, gate: &QMatrix) -> Self {
+    pub fn apply(&self, gate: &QMatrix<T>) -> Self {
         QState {
             state: (0..gate.dims())
-                .into_iter()
                 .map(|row| {
                     let slice =
                         &gate.data_slice()[(gate.dims() * row)..(gate.dims() * row + gate.dims())];
@@ -114,56 +281,292 @@ impl QState {
                         .iter()
                         .zip(self.state.iter())
                         .map(|(&x, &y)| x * y)
-                        .sum::<Complex>()
+                        .sum::<Complex<T>>()
                 })
                 .collect(),
         }
     }
-    pub fn measure(&mut self, bit: usize) -> ClassicalBit {
-        // TODO: Create a lookup table for the indices
-        let mut prob_0_indices = Vec::with_capacity(self.state.len() / 2);
-        let mut prob_1_indices = Vec::with_capacity(self.state.len() / 2);
-        let mut adding_prob_0 = true;
-        let mut step_counter = 0;
-        let step = 2_u32.pow(bit as u32);
-
-        for i in 0..self.state.len() {
-            match adding_prob_0 {
-                true => prob_0_indices.push(i),
-                false => prob_1_indices.push(i),
-            }
-            step_counter += 1;
-            if step_counter == step {
-                adding_prob_0 = !adding_prob_0;
-            }
+    /// Applies a k-qubit gate to the given qubits, where qubit i is bit i of the basis index.
+    /// `qubits[0]` is the most significant qubit of the gate, so the control of
+    /// `CNOT_GATE` is listed first.
+    pub fn apply_on(&self, gate: &QMatrix<T>, qubits: &[usize]) -> Self {
+        let mask = gate_mask(gate, qubits, self.num_qubits());
+        let offsets = gate_offsets(qubits);
+        let mut state = self.state.clone();
+        let mut scratch = vec![Complex::zero(); gate.dims()];
+        for base in (0..self.state.len()).filter(|i| i & mask == 0) {
+            apply_gate_at(&mut state, gate, &offsets, base, &mut scratch);
         }
-        let prob_0: f64 = prob_0_indices.iter().map(|x| self.state[*x].prob()).sum();
-        let prob_1: f64 = prob_1_indices.iter().map(|x| self.state[*x].prob()).sum();
-
-        let measurement_outcome = ClassicalBit::from_probs(prob_0, prob_1);
-        let (delete_indices, normalize_indices) = match measurement_outcome {
-            ClassicalBit::Off => (prob_1_indices, prob_0_indices),
-            ClassicalBit::On => (prob_0_indices, prob_1_indices),
-        };
-        for i in delete_indices {
-            self.state[i] = ZERO;
+        QState { state }
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.state.len().trailing_zeros() as usize
+    }
+    /// The Bloch vector of the reduced state of `qubit`, found by tracing out the
+    /// other qubits. Its length is below 1 when the qubit is entangled with the rest.
+    pub fn bloch_vector(&self, qubit: usize) -> (f64, f64, f64) {
+        reduced_bloch_vector(&self.state, qubit)
+    }
+    /// Measures a qubit in the computational basis and collapses the state. Panics if
+    /// the outcome probabilities are further than `PROBABILITY_TOLERANCE` from summing to one.
+    pub fn measure(&mut self, qubit: usize) -> ClassicalBit {
+        self.measure_qubits(&[qubit])[0]
+    }
+    /// Measures a qubit in the orthonormal basis given by the columns of a 2x2 unitary,
+    /// by rotating the basis onto the computational basis with its adjoint, measuring,
+    /// and rotating back. Outcome 0 leaves the qubit in the first basis vector.
+    pub fn measure_in_basis(&mut self, qubit: usize, basis: &QMatrix<T>) -> ClassicalBit {
+        assert_measurement_basis(basis);
+        *self = self.apply_on(&basis.adjoint(), &[qubit]);
+        let outcome = self.measure(qubit);
+        *self = self.apply_on(basis, &[qubit]);
+        outcome
+    }
+    /// Measures the given qubits jointly in the computational basis and collapses the
+    /// state. Bit i of the result is the outcome for `qubits[i]`. The cost is a few
+    /// passes over the state however many qubits are measured; see `measure_amplitudes`.
+    pub fn measure_qubits(&mut self, qubits: &[usize]) -> Bitstring {
+        measure_amplitudes(&mut self.state, qubits)
+    }
+    /// Measures every qubit, leaving the state in the basis state of the outcome.
+    pub fn measure_all(&mut self) -> Bitstring {
+        let qubits: Vec<usize> = (0..self.num_qubits()).collect();
+        self.measure_qubits(&qubits)
+    }
+    /// Projects the given qubits onto an outcome and renormalizes, as if the outcome had
+    /// been measured. Returns the probability of the outcome, which must not be zero.
+    pub fn post_select(&mut self, qubits: &[usize], outcome: &Bitstring) -> f64 {
+        post_select_amplitudes(&mut self.state, qubits, outcome)
+    }
+    /// The probability of each basis state.
+    pub fn probabilities(&self) -> Vec<f64> {
+        self.state.iter().map(|z| z.prob().to_f64()).collect()
+    }
+    /// The probability of measuring `bit` on `qubit`, without collapsing the state.
+    pub fn probability(&self, qubit: usize, bit: ClassicalBit) -> f64 {
+        let mask = qubit_mask(&[qubit], self.num_qubits());
+        outcome_probability(&self.state, mask, bit as usize * mask)
+    }
+    /// The joint distribution of measuring the given qubits, found in one pass over the
+    /// state. Entry k is the probability of the outcome whose bit i is the value of
+    /// `qubits[i]`, matching `Bitstring::value`. The table has 2^k entries, so this is
+    /// meant for a few qubits; `measure_qubits` and `post_select` do not build it.
+    pub fn marginal_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        marginal_distribution(&self.state, qubits)
+    }
+    /// Resets a qubit to |0⟩ by measuring it and flipping it if the outcome was 1.
+    pub fn reset(&mut self, qubit: usize) {
+        reset_amplitudes(&mut self.state, qubit)
+    }
+}
+/// Draws an index with the given probabilities, after renormalizing them. Panics if
+/// they are further than `PROBABILITY_TOLERANCE` from summing to one.
+pub(crate) fn sample_outcome(probs: &[f64]) -> usize {
+    sample_weighted(probs.iter().copied())
+}
+/// Draws a basis index with the Born probabilities of the amplitudes, with one pass to
+/// check their norm and one cumulative walk.
+pub(crate) fn sample_index<T: Real>(amplitudes: &[Complex<T>]) -> usize {
+    sample_weighted(amplitudes.iter().map(|z| z.prob().to_f64()))
+}
+fn sample_weighted(probs: impl Iterator<Item = f64> + Clone) -> usize {
+    let total: f64 = probs
+        .clone()
+        .inspect(|&prob| assert!(prob >= 0.0, "probabilities must not be negative"))
+        .sum();
+    assert!(
+        (total - 1.0).abs() <= PROBABILITY_TOLERANCE,
+        "probabilities sum to {total} rather than 1"
+    );
+    let mut remaining = thread_rng().gen_range(0.0..total);
+    let mut last_likely = 0;
+    for (outcome, prob) in probs.enumerate() {
+        if remaining < prob {
+            return outcome;
         }
-        let normalization_factor = normalize_indices
-            .iter()
-            .map(|x| self.state[*x].prob())
-            .sum::<f64>()
-            .sqrt();
-        for i in normalize_indices {
-            self.state[i] /= normalization_factor;
+        remaining -= prob;
+        if prob > 0.0 {
+            last_likely = outcome;
+        }
+    }
+    // Rounding can leave a sliver past the last outcome; take the last likely one.
+    last_likely
+}
+/// Measures the given qubits of a state vector jointly and collapses it. A basis index
+/// is drawn from the whole state and its bits on `qubits` form the outcome, whose
+/// marginal distribution is the right one, so no table of outcomes is built.
+pub(crate) fn measure_amplitudes<T: Real>(
+    amplitudes: &mut [Complex<T>],
+    qubits: &[usize],
+) -> Bitstring {
+    let mask = qubit_mask(qubits, amplitudes.len().trailing_zeros() as usize);
+    let index = sample_index(amplitudes);
+    let prob = outcome_probability(amplitudes, mask, index & mask);
+    project(amplitudes, mask, index & mask, prob);
+    Bitstring::from_value(gather_bits(qubits, index), qubits.len())
+}
+/// Projects the given qubits of a state vector onto an outcome and renormalizes.
+/// Returns the probability of the outcome, which must not be zero.
+pub(crate) fn post_select_amplitudes<T: Real>(
+    amplitudes: &mut [Complex<T>],
+    qubits: &[usize],
+    outcome: &Bitstring,
+) -> f64 {
+    assert_eq!(qubits.len(), outcome.len());
+    let mask = qubit_mask(qubits, amplitudes.len().trailing_zeros() as usize);
+    let kept = scatter_bits(qubits, outcome.value());
+    let prob = outcome_probability(amplitudes, mask, kept);
+    assert!(prob > 0.0, "the post-selected outcome has probability zero");
+    project(amplitudes, mask, kept, prob);
+    prob
+}
+/// Measures a qubit of a state vector and flips it to |0⟩ if the outcome was 1.
+pub(crate) fn reset_amplitudes<T: Real>(amplitudes: &mut [Complex<T>], qubit: usize) {
+    if measure_amplitudes(amplitudes, &[qubit])[0] == ClassicalBit::On {
+        let mask = 1 << qubit;
+        for i in (0..amplitudes.len()).filter(|i| i & mask == 0) {
+            amplitudes.swap(i, i | mask);
+        }
+    }
+}
+/// The joint distribution of the given qubits of a state vector, indexed like
+/// `Bitstring::value`.
+pub(crate) fn marginal_distribution<T: Real>(
+    amplitudes: &[Complex<T>],
+    qubits: &[usize],
+) -> Vec<f64> {
+    qubit_mask(qubits, amplitudes.len().trailing_zeros() as usize);
+    let mut probs = vec![0.0; 1 << qubits.len()];
+    for (i, amplitude) in amplitudes.iter().enumerate() {
+        probs[gather_bits(qubits, i)] += amplitude.prob().to_f64();
+    }
+    probs
+}
+/// The Bloch vector of the reduced state of `qubit` in a state vector.
+pub(crate) fn reduced_bloch_vector<T: Real>(
+    amplitudes: &[Complex<T>],
+    qubit: usize,
+) -> (f64, f64, f64) {
+    qubit_mask(&[qubit], amplitudes.len().trailing_zeros() as usize);
+    let mask = 1 << qubit;
+    let (mut prob_0, mut prob_1, mut coherence) = (0.0, 0.0, ZERO);
+    for i in (0..amplitudes.len()).filter(|i| i & mask == 0) {
+        let (amplitude_0, amplitude_1): (Complex, Complex) =
+            (amplitudes[i].cast(), amplitudes[i | mask].cast());
+        prob_0 += amplitude_0.prob();
+        prob_1 += amplitude_1.prob();
+        coherence += amplitude_0.conj() * amplitude_1;
+    }
+    (2.0 * coherence.re, 2.0 * coherence.im, prob_0 - prob_1)
+}
+/// Checks that a measurement basis is a 2x2 unitary.
+pub(crate) fn assert_measurement_basis<T: Real>(basis: &QMatrix<T>) {
+    assert_eq!(basis.dims(), 2);
+    assert!(
+        basis
+            .mul(&basis.adjoint())
+            .approx_eq(&QMatrix::identity(2), 1e-6),
+        "the basis must be unitary"
+    );
+}
+/// The probability that the qubits in `mask` hold the bits of `kept`.
+pub(crate) fn outcome_probability<T: Real>(
+    amplitudes: &[Complex<T>],
+    mask: usize,
+    kept: usize,
+) -> f64 {
+    amplitudes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i & mask == kept)
+        .map(|(_, z)| z.prob().to_f64())
+        .sum()
+}
+/// Keeps the amplitudes whose bits in `mask` equal `kept` and divides them by the
+/// square root of the probability of that outcome.
+pub(crate) fn project<T: Real>(amplitudes: &mut [Complex<T>], mask: usize, kept: usize, prob: f64) {
+    let norm = T::from_f64(prob.sqrt());
+    for (i, amplitude) in amplitudes.iter_mut().enumerate() {
+        match i & mask == kept {
+            true => *amplitude /= norm,
+            false => *amplitude = Complex::zero(),
         }
-        measurement_outcome
+    }
+}
+/// The bit mask of the given qubits, which must be distinct and below `num_qubits`.
+pub(crate) fn qubit_mask(qubits: &[usize], num_qubits: usize) -> usize {
+    assert!(qubits.iter().all(|&q| q < num_qubits));
+    let mask: usize = qubits.iter().map(|&q| 1 << q).sum();
+    assert_eq!(
+        mask.count_ones() as usize,
+        qubits.len(),
+        "qubits must be distinct"
+    );
+    mask
+}
+/// Bit j of the result is bit `qubits[j]` of the basis index, as in `Bitstring::value`.
+pub(crate) fn gather_bits(qubits: &[usize], index: usize) -> usize {
+    qubits
+        .iter()
+        .enumerate()
+        .map(|(j, &q)| (index >> q & 1) << j)
+        .sum()
+}
+/// The basis index bits of an outcome, the inverse of `gather_bits`.
+pub(crate) fn scatter_bits(qubits: &[usize], outcome: usize) -> usize {
+    qubits
+        .iter()
+        .enumerate()
+        .map(|(j, &q)| (outcome >> j & 1) << q)
+        .sum()
+}
+/// Checks that a gate fits the given distinct qubits and returns their bit mask.
+pub(crate) fn gate_mask<T: Real>(gate: &QMatrix<T>, qubits: &[usize], num_qubits: usize) -> usize {
+    let k = qubits.len();
+    assert_eq!(gate.dims(), 1 << k);
+    assert!(qubits.iter().all(|&q| q < num_qubits));
+    let mask: usize = qubits.iter().map(|&q| 1 << q).sum();
+    assert_eq!(mask.count_ones() as usize, k, "qubits must be distinct");
+    mask
+}
+/// offsets[j] spreads the bits of the gate index j onto the target qubits, with
+/// `qubits[0]` taking the most significant bit.
+pub(crate) fn gate_offsets(qubits: &[usize]) -> Vec<usize> {
+    let k = qubits.len();
+    (0..1 << k)
+        .map(|j| {
+            (0..k)
+                .filter(|i| j >> (k - 1 - i) & 1 == 1)
+                .map(|i| 1 << qubits[i])
+                .sum()
+        })
+        .collect()
+}
+/// Applies the gate in place to the amplitudes at `base | offsets[j]`. `scratch` must
+/// have room for one amplitude per gate row.
+pub(crate) fn apply_gate_at<T: Real>(
+    state: &mut [Complex<T>],
+    gate: &QMatrix<T>,
+    offsets: &[usize],
+    base: usize,
+    scratch: &mut [Complex<T>],
+) {
+    for (input, &offset) in scratch.iter_mut().zip(offsets) {
+        *input = state[base | offset];
+    }
+    for (row, &row_offset) in offsets.iter().enumerate() {
+        let slice = &gate.data_slice()[(gate.dims() * row)..(gate.dims() * row + gate.dims())];
+        state[base | row_offset] = slice.iter().zip(scratch.iter()).map(|(&x, &y)| x * y).sum();
     }
 }
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use circuit::Gate;
     use constants::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::f64::consts::PI;
 
     #[test]
     fn state_tensor_of_two_qubit_states_yields_correct_output() {
@@ -234,6 +637,310 @@ mod tests {
         assert!(result.equals(&expected_result));
     }
     #[test]
+    fn applying_cnot_on_reversed_qubits_matches_swapped_full_matrix() {
+        let state = QState::from_qubits(&[Qubit::new(C_IR2, C_IR2), Qubit::new(ONE, ZERO)]);
+        let result = state.apply_on(&CNOT_GATE, &[0, 1]);
+        let expected_result = state.apply(&SWAP_GATE).apply(&CNOT_GATE).apply(&SWAP_GATE);
+        assert!(result.equals(&expected_result));
+    }
+    #[test]
+    fn applying_single_qubit_gate_on_qubit_matches_kronecker_product() {
+        let state = QState::from_qubits(&[
+            Qubit::new(C_IR2, complex!(0.0, IR2)),
+            Qubit::new(ONE, ZERO),
+            Qubit::new(complex!(0.6, 0.0), complex!(0.0, 0.8)),
+        ]);
+        let result = state.apply_on(&H_GATE, &[1]);
+        let expected_result = state.apply(&I_GATE.kronecker(&H_GATE).kronecker(&I_GATE));
+        assert!(result.equals(&expected_result));
+    }
+    #[test]
+    fn bloch_vectors_of_basis_and_superposition_states_are_axes() {
+        let close = |(x, y, z): (f64, f64, f64), (ex, ey, ez): (f64, f64, f64)| {
+            (x - ex).abs() < 1e-12 && (y - ey).abs() < 1e-12 && (z - ez).abs() < 1e-12
+        };
+        assert!(close(Qubit::new(ONE, ZERO).bloch_vector(), (0.0, 0.0, 1.0)));
+        assert!(close(
+            Qubit::new(ZERO, ONE).bloch_vector(),
+            (0.0, 0.0, -1.0)
+        ));
+        assert!(close(
+            Qubit::new(C_IR2, C_IR2).bloch_vector(),
+            (1.0, 0.0, 0.0)
+        ));
+        let plus_i = Qubit::new(C_IR2, complex!(0.0, IR2));
+        assert!(close(plus_i.bloch_vector(), (0.0, 1.0, 0.0)));
+    }
+    #[test]
+    fn qubit_from_bloch_angles_round_trips() {
+        let (theta, phi) = (1.1, -2.3);
+        let (x, y, z) = Qubit::from_bloch(theta, phi).bloch_vector();
+        assert!((x - theta.sin() * phi.cos()).abs() < 1e-12);
+        assert!((y - theta.sin() * phi.sin()).abs() < 1e-12);
+        assert!((z - theta.cos()).abs() < 1e-12);
+    }
+    #[test]
+    fn reduced_bloch_vector_of_product_state_matches_qubit() {
+        let qubit = Qubit::from_bloch(0.7, 0.4);
+        let state = QState::from_qubits(&[Qubit::new(C_IR2, C_IR2), qubit, Qubit::new(ONE, ZERO)]);
+        let (x, y, z) = state.bloch_vector(1);
+        let (ex, ey, ez) = qubit.bloch_vector();
+        assert!((x - ex).abs() < 1e-12 && (y - ey).abs() < 1e-12 && (z - ez).abs() < 1e-12);
+    }
+    #[test]
+    fn reduced_bloch_vectors_of_bell_state_vanish() {
+        let bell = QState::basis_state(2, 0)
+            .apply_on(&H_GATE, &[0])
+            .apply_on(&CNOT_GATE, &[0, 1]);
+        for qubit in 0..2 {
+            let (x, y, z) = bell.bloch_vector(qubit);
+            assert!((x * x + y * y + z * z).sqrt() < 1e-12);
+        }
+    }
+    #[test]
+    fn bell_state_displays_in_dirac_notation() {
+        let bell = QState::basis_state(2, 0)
+            .apply_on(&H_GATE, &[0])
+            .apply_on(&CNOT_GATE, &[0, 1]);
+        assert_eq!(bell.to_string(), "0.707|00⟩ + 0.707|11⟩");
+        assert_eq!(format!("{:.2}", bell), "0.71|00⟩ + 0.71|11⟩");
+    }
+    #[test]
+    fn state_display_handles_signs_and_complex_amplitudes() {
+        let state = QState::from_data(vec![
+            complex!(-0.5, 0.0),
+            complex!(0.0, -0.5),
+            complex!(0.5, 0.5),
+            complex!(1e-9, 0.0),
+        ]);
+        assert_eq!(state.to_string(), "-0.5|00⟩ - 0.5i|01⟩ + (0.5+0.5i)|10⟩");
+        assert_eq!(
+            format!("{:#.2}", state),
+            "0.5∠3.14|00⟩ + 0.5∠-1.57|01⟩ + 0.71∠0.79|10⟩"
+        );
+    }
+    fn ghz_state(num_qubits: usize) -> QState {
+        (1..num_qubits).fold(
+            QState::basis_state(num_qubits, 0).apply_on(&H_GATE, &[0]),
+            |state, q| state.apply_on(&CNOT_GATE, &[0, q]),
+        )
+    }
+    #[test]
+    fn measuring_all_qubits_of_basis_state_returns_its_index() {
+        let mut state: QState = QState::basis_state(5, 0b10110);
+        let outcome = state.measure_all();
+        assert_eq!(outcome.value(), 0b10110);
+        assert_eq!(outcome.to_string(), "10110");
+        assert_eq!(outcome[1], ClassicalBit::On);
+        assert!(state.equals(&QState::basis_state(5, 0b10110)));
+    }
+    #[test]
+    fn measuring_ghz_qubits_jointly_gives_equal_bits() {
+        for _ in 0..10 {
+            let mut state = ghz_state(3);
+            let outcome = state.measure_qubits(&[2, 0]);
+            assert_eq!(outcome[0], outcome[1]);
+            let expected = QState::basis_state(3, 0b111 * outcome[0] as usize);
+            assert!(state.equals(&expected));
+        }
+    }
+    #[test]
+    fn joint_measurement_follows_born_rule() {
+        // Qubit 0 is 1 with probability 0.25 and qubit 1 with probability 0.75.
+        let state = QState::basis_state(2, 0)
+            .apply_on(&Gate::Ry(PI / 3.0).matrix(), &[0])
+            .apply_on(&Gate::Ry(2.0 * PI / 3.0).matrix(), &[1]);
+        let shots = 4000;
+        let mut counts = [0; 4];
+        for _ in 0..shots {
+            counts[state.clone().measure_qubits(&[0, 1]).value()] += 1;
+        }
+        let expected = [0.1875, 0.0625, 0.5625, 0.1875];
+        for (count, expected) in counts.iter().zip(expected) {
+            assert!((*count as f64 / shots as f64 - expected).abs() < 0.03);
+        }
+    }
+    #[test]
+    fn reset_returns_qubit_to_zero_and_collapses_partner() {
+        for _ in 0..10 {
+            let mut state = ghz_state(2);
+            state.reset(1);
+            let (_, _, z) = state.bloch_vector(1);
+            assert!((z - 1.0).abs() < 1e-12);
+            assert!(state.bloch_vector(0).2.abs() > 1.0 - 1e-12);
+        }
+    }
+    fn random_state(num_qubits: usize, rng: &mut impl Rng) -> QState {
+        let amplitudes: Vec<Complex> = (0..1 << num_qubits)
+            .map(|_| complex!(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+            .collect();
+        let norm = amplitudes.iter().map(|z| z.prob()).sum::<f64>().sqrt();
+        QState::from_data(amplitudes.into_iter().map(|z| z / norm).collect())
+    }
+    #[test]
+    fn measuring_every_qubit_matches_marginals_and_projection() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let shots = 400;
+        for num_qubits in 1..=12 {
+            let state = random_state(num_qubits, &mut rng);
+            for qubit in 0..num_qubits {
+                let mask = 1 << qubit;
+                let marginal: f64 = (0..state.state.len())
+                    .filter(|i| i & mask != 0)
+                    .map(|i| state.state[i].prob())
+                    .sum();
+                let mut ones = 0;
+                for shot in 0..shots {
+                    let mut collapsed = state.clone();
+                    let outcome = collapsed.measure(qubit);
+                    ones += outcome as usize;
+                    if shot >= 10 {
+                        continue;
+                    }
+                    let (kept, prob) = match outcome {
+                        ClassicalBit::Off => (0, 1.0 - marginal),
+                        ClassicalBit::On => (mask, marginal),
+                    };
+                    for (i, amplitude) in collapsed.state.iter().enumerate() {
+                        let expected = match i & mask == kept {
+                            true => state.state[i] / prob.sqrt(),
+                            false => ZERO,
+                        };
+                        assert!((*amplitude - expected).mag() < 1e-9);
+                    }
+                }
+                let frequency = ones as f64 / shots as f64;
+                assert!(
+                    (frequency - marginal).abs() < 0.12,
+                    "qubit {qubit} of {num_qubits}: frequency {frequency}, marginal {marginal}"
+                );
+            }
+        }
+    }
+    #[test]
+    fn measuring_many_qubits_collapses_onto_the_sampled_basis_states() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let state = random_state(14, &mut rng);
+        let mut all = state.clone();
+        let outcome = all.measure_all();
+        assert!(state.state[outcome.value()].prob() > 0.0);
+        assert!((all.state[outcome.value()].prob() - 1.0).abs() < 1e-9);
+        // Qubits out of order: bit j of the outcome belongs to qubits[j].
+        let qubits = [13, 2, 7, 0, 11, 5, 9, 3, 12, 1];
+        let mut partial = state.clone();
+        let outcome = partial.measure_qubits(&qubits);
+        let kept = scatter_bits(&qubits, outcome.value());
+        assert_eq!(gather_bits(&qubits, kept), outcome.value());
+        let mask = qubit_mask(&qubits, 14);
+        let prob = outcome_probability(&state.state, mask, kept);
+        for (i, amplitude) in partial.state.iter().enumerate() {
+            let expected = match i & mask == kept {
+                true => state.state[i] / prob.sqrt(),
+                false => ZERO,
+            };
+            assert!((*amplitude - expected).mag() < 1e-9);
+        }
+    }
+    #[test]
+    fn marginals_sum_basis_probabilities_over_other_qubits() {
+        let mut rng = StdRng::seed_from_u64(44);
+        let state = random_state(5, &mut rng);
+        let probs = state.probabilities();
+        assert!((probs.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        let qubits = [3, 0, 4];
+        let marginal = state.marginal_probabilities(&qubits);
+        for (outcome, prob) in marginal.iter().enumerate() {
+            let expected: f64 = (0..32)
+                .filter(|&i| {
+                    qubits
+                        .iter()
+                        .enumerate()
+                        .all(|(j, &q)| (i >> q & 1) == (outcome >> j & 1))
+                })
+                .map(|i| probs[i])
+                .sum();
+            assert!((prob - expected).abs() < 1e-12);
+        }
+        let prob_1: f64 = (0..32).filter(|i| i & 2 != 0).map(|i| probs[i]).sum();
+        assert!((state.probability(1, ClassicalBit::On) - prob_1).abs() < 1e-12);
+        assert!((state.probability(1, ClassicalBit::Off) - (1.0 - prob_1)).abs() < 1e-12);
+    }
+    #[test]
+    fn post_selecting_ghz_qubit_returns_its_probability() {
+        let mut state = ghz_state(3);
+        let outcome = Bitstring::from_bits(vec![ClassicalBit::On]);
+        let prob = state.post_select(&[1], &outcome);
+        assert!((prob - 0.5).abs() < 1e-12);
+        assert!(state.equals(&QState::basis_state(3, 0b111)));
+    }
+    #[test]
+    #[should_panic(expected = "probability zero")]
+    fn post_selecting_impossible_outcome_panics() {
+        let mut state = ghz_state(2);
+        state.post_select(&[0, 1], &Bitstring::from_value(0b01, 2));
+    }
+    #[test]
+    fn certain_outcomes_are_always_drawn() {
+        for _ in 0..100 {
+            assert_eq!(ClassicalBit::from_probs(1.0, 0.0), ClassicalBit::Off);
+            assert_eq!(ClassicalBit::from_probs(0.0, 1.0), ClassicalBit::On);
+        }
+    }
+    #[test]
+    #[should_panic(expected = "rather than 1")]
+    fn unnormalized_probabilities_panic() {
+        ClassicalBit::from_probs(0.5, 0.2);
+    }
+    #[test]
+    #[should_panic(expected = "rather than 1")]
+    fn measuring_unnormalized_state_panics() {
+        let mut state: QState = QState::from_data(vec![ONE, ONE]);
+        state.measure(0);
+    }
+    #[test]
+    fn measuring_in_eigenbasis_leaves_state_unchanged() {
+        // The columns of S·H are |+i⟩ and |-i⟩.
+        let y_basis = H_GATE.mul(&S_GATE);
+        let plus_i = QState::basis_state(2, 0).apply_on(&y_basis, &[1]);
+        for _ in 0..10 {
+            let mut state = plus_i.clone();
+            assert_eq!(state.measure_in_basis(1, &y_basis), ClassicalBit::Off);
+            assert!(state.equals(&plus_i));
+            let mut state = QState::basis_state(1, 0).apply(&H_GATE).apply(&Z_GATE);
+            assert_eq!(state.measure_in_basis(0, &H_GATE), ClassicalBit::On);
+            let (x, _, _) = state.bloch_vector(0);
+            assert!((x + 1.0).abs() < 1e-12);
+        }
+    }
+    #[test]
+    fn single_precision_state_matches_double_precision() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let gates: Vec<(QMatrix, Vec<usize>)> = (0..12)
+            .map(|k| match k % 3 {
+                2 => (QMatrix::haar_random(4, &mut rng), vec![k % 5, (k + 2) % 5]),
+                _ => (QMatrix::haar_random(2, &mut rng), vec![k % 5]),
+            })
+            .collect();
+        let mut double = QState::basis_state(5, 0);
+        let mut single = QState::<f32>::basis_state(5, 0);
+        for (gate, qubits) in &gates {
+            double = double.apply_on(gate, qubits);
+            single = single.apply_on(&gate.cast(), qubits);
+        }
+        for (a, b) in single
+            .cast::<f64>()
+            .data_slice()
+            .iter()
+            .zip(double.data_slice())
+        {
+            assert!((*a - *b).mag() < 1e-5);
+        }
+        let outcome = single.measure(2);
+        let expected_z = 1.0 - 2.0 * outcome as usize as f64;
+        assert!((single.bloch_vector(2).2 - expected_z).abs() < 1e-6);
+        assert_eq!(std::mem::size_of::<Complex<f32>>(), 8);
+    }
+    #[test]
     fn measuring_single_qubit_collapses_quantum_state() {
         let mut state = QState::from_qubits(&[
             Qubit::new(I, ZERO),
@@ -0,0 +1,483 @@
+use crate::complex;
+use crate::complex::Complex;
+use crate::matrix::QMatrix;
+use crate::QuantumVec;
+
+/// Diagonalizes a real symmetric n x n matrix, stored row major, with cyclic Jacobi rotations.
+/// Returns the eigenvalues and a row-major orthogonal matrix whose columns are the
+/// corresponding eigenvectors. Panics if the rotations do not converge.
+pub(crate) fn symmetric_eigen(matrix: &[f64], n: usize) -> (Vec<f64>, Vec<f64>) {
+    assert_eq!(matrix.len(), n * n);
+    let mut a = matrix.to_vec();
+    let mut v = vec![0.0; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.0;
+    }
+    let norm_squared: f64 = a.iter().map(|x| x * x).sum();
+    let mut converged = false;
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i * n + j].powi(2))
+            .sum();
+        if off_diagonal <= 1e-30 * norm_squared {
+            converged = true;
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[p * n + q];
+                if apq.abs() < 1e-300 {
+                    continue;
+                }
+                // Choose the rotation angle that zeroes a[p][q].
+                let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let akp = a[k * n + p];
+                    let akq = a[k * n + q];
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let apk = a[p * n + k];
+                    let aqk = a[q * n + k];
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    assert!(
+        converged,
+        "the symmetric eigendecomposition did not converge"
+    );
+    ((0..n).map(|i| a[i * n + i]).collect(), v)
+}
+/// Diagonalizes a Hermitian matrix with complex Jacobi rotations, each of which first
+/// makes the pivot real with a phase and then zeroes it as in `symmetric_eigen`.
+/// Returns the eigenvalues in ascending order and a unitary matrix whose columns are
+/// the corresponding eigenvectors. Panics if the rotations do not converge.
+pub(crate) fn hermitian_eigen(matrix: &QMatrix) -> (Vec<f64>, QMatrix) {
+    let n = matrix.dims();
+    let mut a = matrix.data_slice().to_vec();
+    let mut v = QMatrix::identity(n).data_slice().to_vec();
+    // Rotations preserve the Frobenius norm, so converging relative to it is independent
+    // of the scale of the matrix.
+    let norm_squared: f64 = a.iter().map(|z| z.prob()).sum();
+    let mut converged = false;
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i * n + j].prob())
+            .sum();
+        if off_diagonal <= 1e-30 * norm_squared {
+            converged = true;
+            break;
+        }
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[p * n + q];
+                if apq.mag() < 1e-300 {
+                    continue;
+                }
+                let u = jacobi_rotation(a[p * n + p].re, a[q * n + q].re, apq);
+                rotate_columns(&mut a, n, p, q, u);
+                for k in 0..n {
+                    let (apk, aqk) = (a[p * n + k], a[q * n + k]);
+                    a[p * n + k] = u[0].conj() * apk + u[2].conj() * aqk;
+                    a[q * n + k] = u[1].conj() * apk + u[3].conj() * aqk;
+                }
+                rotate_columns(&mut v, n, p, q, u);
+            }
+        }
+    }
+    assert!(
+        converged,
+        "the Hermitian eigendecomposition did not converge"
+    );
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[i * n + i].re.total_cmp(&a[j * n + j].re));
+    let values = order.iter().map(|&i| a[i * n + i].re).collect();
+    let vectors = (0..n * n).map(|i| v[i / n * n + order[i % n]]).collect();
+    (values, QMatrix::from_data(vectors))
+}
+/// The singular value decomposition A = U·Σ·V† of a complex rows x cols matrix, stored
+/// row major, by one-sided Jacobi rotations that make the columns of A·V orthogonal.
+/// Returns U as a rows x cols matrix, the singular values in descending order, and the
+/// unitary V as a cols x cols matrix, both row major. Columns of U belonging to
+/// vanishing singular values are completed to an orthonormal set, which needs at least
+/// as many rows as columns; fewer columns than rows also keeps the decomposition cheap.
+pub(crate) fn complex_svd(
+    matrix: &[Complex],
+    rows: usize,
+    cols: usize,
+) -> (Vec<Complex>, Vec<f64>, Vec<Complex>) {
+    assert_eq!(matrix.len(), rows * cols);
+    let mut a = matrix.to_vec();
+    let mut v: Vec<Complex> = (0..cols * cols)
+        .map(|i| match i / cols == i % cols {
+            true => Complex::one(),
+            false => Complex::zero(),
+        })
+        .collect();
+    let column_product = |a: &[Complex], p: usize, q: usize| -> Complex {
+        (0..rows)
+            .map(|k| a[k * cols + p].conj() * a[k * cols + q])
+            .sum()
+    };
+    let mut converged = false;
+    for _ in 0..100 {
+        let mut rotated = false;
+        for p in 0..cols {
+            for q in (p + 1)..cols {
+                let gamma = column_product(&a, p, q);
+                let (alpha, beta) = (column_product(&a, p, p).re, column_product(&a, q, q).re);
+                if gamma.mag() <= 1e-15 * (alpha * beta).sqrt() || gamma.mag() < 1e-300 {
+                    continue;
+                }
+                // Rotating the columns diagonalizes this block of the Gram matrix A†A.
+                let u = jacobi_rotation(alpha, beta, gamma);
+                rotate_columns(&mut a, cols, p, q, u);
+                rotate_columns(&mut v, cols, p, q, u);
+                rotated = true;
+            }
+        }
+        if !rotated {
+            converged = true;
+            break;
+        }
+    }
+    assert!(
+        converged,
+        "the singular value decomposition did not converge"
+    );
+    let norms: Vec<f64> = (0..cols)
+        .map(|j| column_product(&a, j, j).re.sqrt())
+        .collect();
+    let mut order: Vec<usize> = (0..cols).collect();
+    order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+    let values: Vec<f64> = order.iter().map(|&j| norms[j]).collect();
+    let largest = values.first().copied().unwrap_or(0.0);
+    let rank = values
+        .iter()
+        .take_while(|&&value| value > 1e-12 * largest)
+        .count();
+    let mut u: Vec<Complex> = (0..rows * cols)
+        .map(|i| match i % cols < rank {
+            true => a[i / cols * cols + order[i % cols]] / values[i % cols],
+            false => Complex::zero(),
+        })
+        .collect();
+    for col in rank..cols.min(rows) {
+        // Orthogonalize basis vectors against the columns found so far until one survives.
+        for k in 0..rows {
+            let mut candidate: Vec<Complex> = (0..rows)
+                .map(|row| match row == k {
+                    true => Complex::one(),
+                    false => Complex::zero(),
+                })
+                .collect();
+            for previous in 0..col {
+                let overlap: Complex = (0..rows)
+                    .map(|row| u[row * cols + previous].conj() * candidate[row])
+                    .sum();
+                for (row, entry) in candidate.iter_mut().enumerate() {
+                    *entry -= overlap * u[row * cols + previous];
+                }
+            }
+            let norm = candidate.iter().map(|z| z.prob()).sum::<f64>().sqrt();
+            if norm > 0.5 {
+                for (row, entry) in candidate.into_iter().enumerate() {
+                    u[row * cols + col] = entry / norm;
+                }
+                break;
+            }
+        }
+    }
+    let v = (0..cols * cols)
+        .map(|i| v[i / cols * cols + order[i % cols]])
+        .collect();
+    (u, values, v)
+}
+/// The complex Schur decomposition A = Q·T·Q† of a square matrix, with Q unitary and
+/// T upper triangular, by reduction to Hessenberg form followed by QR iterations with
+/// Wilkinson shifts. T is diagonal when A is normal, such as a unitary or Hermitian
+/// matrix, in which case the columns of Q are eigenvectors. Returns (Q, T).
+pub(crate) fn complex_schur(matrix: &QMatrix) -> (QMatrix, QMatrix) {
+    let n = matrix.dims();
+    let mut t = matrix.data_slice().to_vec();
+    let mut q = QMatrix::identity(n).data_slice().to_vec();
+    // Rotating rows k and k + 1 together with the matching columns keeps the similarity.
+    let mut rotate = |t: &mut [Complex], k: usize, c: f64, s: Complex| {
+        for col in 0..n {
+            let (x, y) = (t[k * n + col], t[(k + 1) * n + col]);
+            t[k * n + col] = c * x + s * y;
+            t[(k + 1) * n + col] = c * y - s.conj() * x;
+        }
+        let g = [complex!(c, 0.0), -s, s.conj(), complex!(c, 0.0)];
+        rotate_columns(t, n, k, k + 1, g);
+        rotate_columns(&mut q, n, k, k + 1, g);
+    };
+    for col in 0..n.saturating_sub(2) {
+        for row in ((col + 2)..n).rev() {
+            let (c, s) = givens(t[(row - 1) * n + col], t[row * n + col]);
+            rotate(&mut t, row - 1, c, s);
+        }
+    }
+    let mut end = n.saturating_sub(1);
+    let mut iterations = 0;
+    while end > 0 {
+        let small = |t: &[Complex], k: usize| {
+            t[k * n + k - 1].mag()
+                <= 1e-15 * (t[k * n + k].mag() + t[(k - 1) * n + k - 1].mag()).max(1e-300)
+        };
+        if small(&t, end) {
+            t[end * n + end - 1] = Complex::zero();
+            end -= 1;
+            iterations = 0;
+            continue;
+        }
+        let start = (1..end).rev().find(|&k| small(&t, k)).unwrap_or(0);
+        iterations += 1;
+        assert!(
+            iterations < 1000,
+            "the Schur decomposition did not converge"
+        );
+        // The eigenvalue of the trailing 2x2 block closest to its last entry, or an
+        // exceptional shift to break cycles.
+        let (a, b) = (t[(end - 1) * n + end - 1], t[(end - 1) * n + end]);
+        let (c, d) = (t[end * n + end - 1], t[end * n + end]);
+        let shift = match iterations % 11 {
+            0 => d + complex!(t[end * n + end - 1].mag(), 0.0),
+            _ => {
+                let half_trace = (a + d) / 2.0;
+                let root = ((a - d) * (a - d) / 4.0 + b * c).sqrt();
+                let (plus, minus) = (half_trace + root, half_trace - root);
+                match (plus - d).mag() < (minus - d).mag() {
+                    true => plus,
+                    false => minus,
+                }
+            }
+        };
+        for k in start..=end {
+            t[k * n + k] -= shift;
+        }
+        let mut rotations = Vec::with_capacity(end - start);
+        for k in start..end {
+            let (c, s) = givens(t[k * n + k], t[(k + 1) * n + k]);
+            for col in 0..n {
+                let (x, y) = (t[k * n + col], t[(k + 1) * n + col]);
+                t[k * n + col] = c * x + s * y;
+                t[(k + 1) * n + col] = c * y - s.conj() * x;
+            }
+            rotations.push((k, c, s));
+        }
+        for (k, c, s) in rotations {
+            let g = [complex!(c, 0.0), -s, s.conj(), complex!(c, 0.0)];
+            rotate_columns(&mut t, n, k, k + 1, g);
+            rotate_columns(&mut q, n, k, k + 1, g);
+        }
+        for k in start..=end {
+            t[k * n + k] += shift;
+        }
+    }
+    // Entries below the diagonal are rounding noise once the iteration has converged.
+    for row in 1..n {
+        for col in 0..row {
+            t[row * n + col] = Complex::zero();
+        }
+    }
+    (QMatrix::from_data(q), QMatrix::from_data(t))
+}
+/// The rotation (c, s) with real c for which [[c, s], [-conj(s), c]]·[a, b] = [r, 0].
+fn givens(a: Complex, b: Complex) -> (f64, Complex) {
+    let norm = (a.prob() + b.prob()).sqrt();
+    match (a.mag() > 0.0, norm > 0.0) {
+        (true, _) => (a.mag() / norm, a / a.mag() * b.conj() / norm),
+        (false, true) => (0.0, Complex::one()),
+        (false, false) => (1.0, Complex::zero()),
+    }
+}
+/// The 2x2 unitary `[upp, upq, uqp, uqq]` that diagonalizes the Hermitian block
+/// `[[app, apq], [conj(apq), aqq]]` as U†·block·U. It first makes `apq` real with a
+/// phase and then applies the real rotation of `symmetric_eigen`.
+fn jacobi_rotation(app: f64, aqq: f64, apq: Complex) -> [Complex; 4] {
+    let (r, phase) = (apq.mag(), Complex::cis(-apq.arg()));
+    let theta = (aqq - app) / (2.0 * r);
+    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+    let c = 1.0 / (t * t + 1.0).sqrt();
+    let s = t * c;
+    [complex!(c, 0.0), complex!(s, 0.0), -s * phase, c * phase]
+}
+/// Replaces columns p and q of a row-major matrix with `cols` columns by their
+/// combinations under the 2x2 unitary `[upp, upq, uqp, uqq]`.
+fn rotate_columns(matrix: &mut [Complex], cols: usize, p: usize, q: usize, u: [Complex; 4]) {
+    for row in matrix.chunks_exact_mut(cols) {
+        let (xp, xq) = (row[p], row[q]);
+        row[p] = xp * u[0] + xq * u[2];
+        row[q] = xp * u[1] + xq * u[3];
+    }
+}
+/// Applies a function to the eigenvalues of a Hermitian matrix.
+pub(crate) fn hermitian_function(matrix: &QMatrix, f: impl Fn(f64) -> Complex) -> QMatrix {
+    let (values, vectors) = hermitian_eigen(matrix);
+    let n = matrix.dims();
+    let scaled: Vec<Complex> = (0..n * n)
+        .map(|i| vectors.data_slice()[i] * f(values[i % n]))
+        .collect();
+    vectors.adjoint().mul(&QMatrix::from_data(scaled))
+}
+/// The determinant of a real n x n matrix, stored row major, by Gaussian elimination.
+pub(crate) fn real_determinant(matrix: &[f64], n: usize) -> f64 {
+    let mut a = matrix.to_vec();
+    let mut det = 1.0;
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))
+            .unwrap();
+        if a[pivot * n + col] == 0.0 {
+            return 0.0;
+        }
+        if pivot != col {
+            for k in 0..n {
+                a.swap(pivot * n + k, col * n + k);
+            }
+            det = -det;
+        }
+        det *= a[col * n + col];
+        for row in (col + 1)..n {
+            let factor = a[row * n + col] / a[col * n + col];
+            for k in col..n {
+                a[row * n + k] -= factor * a[col * n + k];
+            }
+        }
+    }
+    det
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn symmetric_eigen_diagonalizes_matrix() {
+        #[rustfmt::skip]
+        let matrix = [
+            4.0, 1.0, -2.0, 2.0,
+            1.0, 2.0, 0.0, 1.0,
+            -2.0, 0.0, 3.0, -2.0,
+            2.0, 1.0, -2.0, -1.0,
+        ];
+        // Convergence is relative to the norm, so a tiny matrix is diagonalized as well.
+        for scale in [1.0, 1e-20] {
+            let matrix = matrix.map(|x| scale * x);
+            let (values, vectors) = symmetric_eigen(&matrix, 4);
+            for (k, value) in values.iter().enumerate() {
+                for row in 0..4 {
+                    let av: f64 = (0..4)
+                        .map(|j| matrix[row * 4 + j] * vectors[j * 4 + k])
+                        .sum();
+                    assert!((av - value * vectors[row * 4 + k]).abs() < 1e-10 * scale);
+                }
+            }
+            let trace: f64 = values.iter().sum();
+            assert!((trace - 8.0 * scale).abs() < 1e-10 * scale);
+        }
+    }
+    #[test]
+    fn hermitian_eigen_diagonalizes_complex_matrix() {
+        let unitary = QMatrix::haar_random(4, &mut StdRng::seed_from_u64(3));
+        let spectrum = QMatrix::from_data(
+            (0..16)
+                .map(|i| match i % 5 {
+                    0 => complex!([1.0, -2.0, 0.5, 3.0][i / 5], 0.0),
+                    _ => complex!(0.0, 0.0),
+                })
+                .collect(),
+        );
+        let matrix = unitary.adjoint().mul(&spectrum).mul(&unitary);
+        let (values, vectors) = hermitian_eigen(&matrix);
+        for (value, expected) in values.iter().zip([-2.0, 0.5, 1.0, 3.0]) {
+            assert!((value - expected).abs() < 1e-10);
+        }
+        let product = vectors.mul(&vectors.adjoint());
+        assert!(product.approx_eq(&QMatrix::identity(4), 1e-10));
+        let diagonal = vectors.mul(&matrix).mul(&vectors.adjoint());
+        let expected = QMatrix::from_data(
+            (0..16)
+                .map(|i| match i % 5 == 0 {
+                    true => complex!(values[i / 5], 0.0),
+                    false => complex!(0.0, 0.0),
+                })
+                .collect(),
+        );
+        assert!(diagonal.approx_eq(&expected, 1e-10));
+        let square_root = hermitian_function(&matrix.mul(&matrix), |x| complex!(x.sqrt(), 0.0));
+        let absolute = hermitian_function(&matrix, |x| complex!(x.abs(), 0.0));
+        assert!(square_root.approx_eq(&absolute, 1e-10));
+    }
+    #[test]
+    fn hermitian_eigen_converges_relative_to_scale() {
+        let unitary = QMatrix::haar_random(4, &mut StdRng::seed_from_u64(5));
+        let spectrum = [1.0, -2.0, 0.5, 3.0];
+        let diagonal = QMatrix::from_data(
+            (0..16)
+                .map(|i| match i % 5 {
+                    0 => complex!(1e-20 * spectrum[i / 5], 0.0),
+                    _ => complex!(0.0, 0.0),
+                })
+                .collect(),
+        );
+        let matrix = unitary.adjoint().mul(&diagonal).mul(&unitary);
+        let (values, _) = hermitian_eigen(&matrix);
+        for (value, expected) in values.iter().zip([-2.0, 0.5, 1.0, 3.0]) {
+            assert!((value / 1e-20 - expected).abs() < 1e-10);
+        }
+    }
+    #[test]
+    fn complex_svd_reconstructs_rectangular_matrix() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (rows, cols) = (5, 3);
+        let matrix: Vec<Complex> = (0..rows * cols)
+            .map(|_| complex!(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+            .collect();
+        let (u, values, v) = complex_svd(&matrix, rows, cols);
+        assert!(values.windows(2).all(|pair| pair[0] >= pair[1]));
+        for row in 0..rows {
+            for col in 0..cols {
+                let entry: Complex = (0..cols)
+                    .map(|k| u[row * cols + k] * values[k] * v[col * cols + k].conj())
+                    .sum();
+                assert!((entry - matrix[row * cols + col]).mag() < 1e-10);
+            }
+        }
+        for (p, q) in (0..cols).flat_map(|p| (0..cols).map(move |q| (p, q))) {
+            let product: Complex = (0..rows)
+                .map(|k| u[k * cols + p].conj() * u[k * cols + q])
+                .sum();
+            let expected = if p == q { 1.0 } else { 0.0 };
+            assert!((product - complex!(expected, 0.0)).mag() < 1e-10);
+        }
+    }
+    #[test]
+    fn complex_svd_of_matrix_without_columns_is_empty() {
+        let (u, values, v) = complex_svd(&[], 4, 0);
+        assert!(u.is_empty() && values.is_empty() && v.is_empty());
+    }
+    #[test]
+    fn real_determinant_of_permutation_is_its_sign() {
+        let matrix = [0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0];
+        assert_eq!(real_determinant(&matrix, 3), 1.0);
+        let matrix = [0.0, 1.0, 1.0, 0.0];
+        assert_eq!(real_determinant(&matrix, 2), -1.0);
+    }
+}
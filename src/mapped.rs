@@ -0,0 +1,285 @@
+use crate::complex::{Complex, Real};
+use crate::matrix::QMatrix;
+use crate::{
+    apply_gate_at, assert_measurement_basis, gate_mask, gate_offsets, marginal_distribution,
+    measure_amplitudes, outcome_probability, post_select_amplitudes, qubit_mask,
+    reduced_bloch_vector, reset_amplitudes, Bitstring, ClassicalBit, QState,
+};
+use memmap2::MmapMut;
+use std::any::TypeId;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// A state vector stored in a memory-mapped file, for states larger than RAM. It has
+/// the gate and measurement methods of `QState`, which act on the file in place.
+///
+/// The file holds the amplitudes as native-endian `re, im` pairs of `T`, `f64` unless
+/// single precision is chosen, in basis-index order, and nothing else. Each gate is
+/// applied in place in one pass over the file.
+///
+/// The file must not be modified or truncated by anything else while it is mapped.
+pub struct MappedState<T: Real = f64> {
+    map: MmapMut,
+    num_qubits: usize,
+    amplitude: PhantomData<Complex<T>>,
+}
+impl<T: Real> MappedState<T> {
+    /// Creates a file at `path` holding the state |0...0⟩, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>, num_qubits: usize) -> io::Result<MappedState<T>> {
+        let mut state = MappedState::allocate(path, num_qubits)?;
+        state.amplitudes_mut()[0] = Complex::one();
+        Ok(state)
+    }
+    /// Copies an in-memory state into a new file at `path`.
+    pub fn from_state(path: impl AsRef<Path>, state: &QState<T>) -> io::Result<MappedState<T>> {
+        let mut mapped = MappedState::allocate(path, state.num_qubits())?;
+        mapped.amplitudes_mut().copy_from_slice(&state.state);
+        Ok(mapped)
+    }
+    /// Maps an existing state file, such as one left by an earlier `MappedState` with
+    /// the same amplitude type.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<MappedState<T>> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let bytes = file.metadata()?.len() as usize;
+        let len = bytes / std::mem::size_of::<Complex<T>>();
+        if !len.is_power_of_two() || len * std::mem::size_of::<Complex<T>>() != bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file size is not a power of two number of amplitudes",
+            ));
+        }
+        MappedState::map(&file, len.trailing_zeros() as usize)
+    }
+    fn allocate(path: impl AsRef<Path>, num_qubits: usize) -> io::Result<MappedState<T>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(((1_u64) << num_qubits) * std::mem::size_of::<Complex<T>>() as u64)?;
+        MappedState::map(&file, num_qubits)
+    }
+    fn map(file: &File, num_qubits: usize) -> io::Result<MappedState<T>> {
+        // `Real` is only meant for f32 and f64, but nothing stops other implementations,
+        // and the casts in `amplitudes` rely on every bit pattern being valid.
+        assert!(
+            TypeId::of::<T>() == TypeId::of::<f64>() || TypeId::of::<T>() == TypeId::of::<f32>(),
+            "mapped amplitudes must be f32 or f64"
+        );
+        // Safety: as documented on `MappedState`, nothing else may modify or truncate the
+        // file while it is mapped.
+        let map = unsafe { MmapMut::map_mut(file)? };
+        Ok(MappedState {
+            map,
+            num_qubits,
+            amplitude: PhantomData,
+        })
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+    pub fn amplitudes(&self) -> &[Complex<T>] {
+        // Safety: the map is page aligned and holds exactly 2^n `Complex<T>` values,
+        // which are `repr(C)` pairs of f32 or f64 for which every bit pattern is valid.
+        unsafe {
+            std::slice::from_raw_parts(self.map.as_ptr() as *const Complex<T>, 1 << self.num_qubits)
+        }
+    }
+    fn amplitudes_mut(&mut self) -> &mut [Complex<T>] {
+        // Safety: as in `amplitudes`.
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                self.map.as_mut_ptr() as *mut Complex<T>,
+                1 << self.num_qubits,
+            )
+        }
+    }
+    /// Copies the state into memory.
+    pub fn to_state(&self) -> QState<T> {
+        QState::from_data(self.amplitudes().to_vec())
+    }
+    /// Writes outstanding changes to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+    /// Applies a k-qubit gate to the given qubits in place, with the same qubit order as
+    /// `QState::apply_on`.
+    pub fn apply_on(&mut self, gate: &QMatrix<T>, qubits: &[usize]) {
+        let mask = gate_mask(gate, qubits, self.num_qubits);
+        let offsets = gate_offsets(qubits);
+        let mut scratch = vec![Complex::zero(); gate.dims()];
+        let state = self.amplitudes_mut();
+        for base in (0..state.len()).filter(|i| i & mask == 0) {
+            apply_gate_at(state, gate, &offsets, base, &mut scratch);
+        }
+    }
+    /// The Bloch vector of the reduced state of `qubit`, as for `QState::bloch_vector`.
+    pub fn bloch_vector(&self, qubit: usize) -> (f64, f64, f64) {
+        reduced_bloch_vector(self.amplitudes(), qubit)
+    }
+    /// Measures a qubit in the computational basis and collapses the state.
+    pub fn measure(&mut self, qubit: usize) -> ClassicalBit {
+        self.measure_qubits(&[qubit])[0]
+    }
+    /// Measures a qubit in the basis given by the columns of a 2x2 unitary, as for
+    /// `QState::measure_in_basis`.
+    pub fn measure_in_basis(&mut self, qubit: usize, basis: &QMatrix<T>) -> ClassicalBit {
+        assert_measurement_basis(basis);
+        self.apply_on(&basis.adjoint(), &[qubit]);
+        let outcome = self.measure(qubit);
+        self.apply_on(basis, &[qubit]);
+        outcome
+    }
+    /// Measures the given qubits jointly and collapses the state, as for
+    /// `QState::measure_qubits`.
+    pub fn measure_qubits(&mut self, qubits: &[usize]) -> Bitstring {
+        measure_amplitudes(self.amplitudes_mut(), qubits)
+    }
+    /// Measures every qubit, leaving the state in the basis state of the outcome.
+    pub fn measure_all(&mut self) -> Bitstring {
+        let qubits: Vec<usize> = (0..self.num_qubits).collect();
+        self.measure_qubits(&qubits)
+    }
+    /// Projects the given qubits onto an outcome and renormalizes, as for
+    /// `QState::post_select`.
+    pub fn post_select(&mut self, qubits: &[usize], outcome: &Bitstring) -> f64 {
+        post_select_amplitudes(self.amplitudes_mut(), qubits, outcome)
+    }
+    /// The probability of each basis state, as an in-memory vector.
+    pub fn probabilities(&self) -> Vec<f64> {
+        self.amplitudes()
+            .iter()
+            .map(|z| z.prob().to_f64())
+            .collect()
+    }
+    /// The probability of measuring `bit` on `qubit`, without collapsing the state.
+    pub fn probability(&self, qubit: usize, bit: ClassicalBit) -> f64 {
+        let mask = qubit_mask(&[qubit], self.num_qubits);
+        outcome_probability(self.amplitudes(), mask, bit as usize * mask)
+    }
+    /// The joint distribution of measuring the given qubits, as for
+    /// `QState::marginal_probabilities`.
+    pub fn marginal_probabilities(&self, qubits: &[usize]) -> Vec<f64> {
+        marginal_distribution(self.amplitudes(), qubits)
+    }
+    /// Resets a qubit to |0⟩ by measuring it and flipping it if the outcome was 1.
+    pub fn reset(&mut self, qubit: usize) {
+        reset_amplitudes(self.amplitudes_mut(), qubit)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::{complex, QuantumVec, Qubit};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::path::PathBuf;
+
+    /// A path in the temporary directory that is removed when dropped.
+    struct TempPath(PathBuf);
+    impl TempPath {
+        fn new(name: &str) -> TempPath {
+            TempPath(
+                std::env::temp_dir().join(format!("quanta-{}-{name}.state", std::process::id())),
+            )
+        }
+    }
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+    #[test]
+    fn mapped_state_matches_in_memory_state() {
+        let path = TempPath::new("gates");
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut mapped = MappedState::create(&path.0, 6).unwrap();
+        let mut state = QState::basis_state(6, 0);
+        for _ in 0..30 {
+            let (a, b) = (rng.gen_range(0..6), rng.gen_range(0..6));
+            let (gate, qubits) = match a == b {
+                true => (QMatrix::haar_random(2, &mut rng), vec![a]),
+                false => (QMatrix::haar_random(4, &mut rng), vec![a, b]),
+            };
+            mapped.apply_on(&gate, &qubits);
+            state = state.apply_on(&gate, &qubits);
+        }
+        for (a, b) in mapped.amplitudes().iter().zip(state.data_slice()) {
+            assert!((*a - *b).mag() < 1e-12);
+        }
+    }
+    #[test]
+    fn measuring_mapped_bell_state_collapses_both_qubits() {
+        let path = TempPath::new("bell");
+        let mut mapped = MappedState::create(&path.0, 2).unwrap();
+        mapped.apply_on(&H_GATE, &[0]);
+        mapped.apply_on(&CNOT_GATE, &[0, 1]);
+        assert!(mapped.bloch_vector(1).2.abs() < 1e-12);
+        let outcome = mapped.measure(0);
+        let expected = QState::basis_state(2, 3 * outcome as usize);
+        assert!(mapped.to_state().equals(&expected));
+    }
+    fn random_state(num_qubits: usize, rng: &mut impl Rng) -> QState {
+        let mut state = QState::basis_state(num_qubits, 0);
+        for a in 0..num_qubits {
+            let b = (a + 1) % num_qubits;
+            state = state.apply_on(&QMatrix::haar_random(4, rng), &[a, b]);
+        }
+        state
+    }
+    #[test]
+    fn mapped_measurements_match_in_memory_state() {
+        let path = TempPath::new("measurements");
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut state = random_state(5, &mut rng);
+        let mut mapped = MappedState::from_state(&path.0, &state).unwrap();
+        assert_eq!(
+            mapped.marginal_probabilities(&[3, 1]),
+            state.marginal_probabilities(&[3, 1])
+        );
+        assert_eq!(
+            mapped.probability(2, ClassicalBit::On),
+            state.probability(2, ClassicalBit::On)
+        );
+        assert_eq!(mapped.bloch_vector(4), state.bloch_vector(4));
+        let outcome = Bitstring::from_value(0b10, 2);
+        assert_eq!(
+            mapped.post_select(&[4, 0], &outcome),
+            state.post_select(&[4, 0], &outcome)
+        );
+        assert!(mapped.to_state().equals(&state));
+        mapped.reset(4);
+        assert_eq!(mapped.probability(4, ClassicalBit::Off), 1.0);
+        let outcome = mapped.measure_all();
+        assert!(state.probabilities()[outcome.value()] > 0.0);
+        assert!((mapped.amplitudes()[outcome.value()].prob() - 1.0).abs() < 1e-12);
+    }
+    #[test]
+    fn single_precision_state_persists_across_open() {
+        let path = TempPath::new("single");
+        let mut rng = StdRng::seed_from_u64(3);
+        let state: QState<f32> = random_state(3, &mut rng).cast();
+        MappedState::from_state(&path.0, &state).unwrap();
+        let mut reopened = MappedState::<f32>::open(&path.0).unwrap();
+        assert_eq!(reopened.num_qubits(), 3);
+        assert!(reopened.to_state().equals(&state));
+        let outcome = reopened.measure_in_basis(1, &H_GATE.cast());
+        let (x, _, _) = reopened.bloch_vector(1);
+        assert!((x - (1.0 - 2.0 * outcome as usize as f64)).abs() < 1e-5);
+    }
+    #[test]
+    fn mapped_state_persists_across_open() {
+        let path = TempPath::new("reopen");
+        let state =
+            QState::from_qubits(&[Qubit::new(C_IR2, complex!(0.0, IR2)), Qubit::new(ONE, ZERO)]);
+        let mapped = MappedState::from_state(&path.0, &state).unwrap();
+        mapped.flush().unwrap();
+        drop(mapped);
+        let reopened = MappedState::open(&path.0).unwrap();
+        assert_eq!(reopened.num_qubits(), 2);
+        assert!(reopened.to_state().equals(&state));
+    }
+}
@@ -1,22 +1,26 @@
-use crate::complex::Complex;
-use crate::{complex, QuantumVec};
+use crate::complex::{Complex, Real, DEFAULT_PRECISION};
+use crate::linalg::{complex_schur, complex_svd, hermitian_eigen, hermitian_function};
+use crate::{complex, QState, QuantumVec};
+use rand::Rng;
+use std::f64::consts::PI;
+use std::fmt;
 use std::ops::Mul;
 #[derive(Debug, Clone, Default, PartialEq)]
 /// A square matrix representing a quantum gate.
 /// Must be of dimensions 2^n x 2^n for some n.
-pub struct QMatrix {
+pub struct QMatrix<T = f64> {
     dims: usize,
-    data: Vec<Complex>,
+    data: Vec<Complex<T>>,
 }
-impl QuantumVec for QMatrix {
-    fn data_slice(&self) -> &[Complex] {
+impl<T: Real> QuantumVec<T> for QMatrix<T> {
+    fn data_slice(&self) -> &[Complex<T>] {
         &self.data
     }
 }
 
-impl Mul<QMatrix> for Complex {
-    type Output = QMatrix;
-    fn mul(self, rhs: QMatrix) -> Self::Output {
+impl<T: Real> Mul<QMatrix<T>> for Complex<T> {
+    type Output = QMatrix<T>;
+    fn mul(self, rhs: QMatrix<T>) -> Self::Output {
         QMatrix {
             dims: rhs.dims,
             data: rhs.data.into_iter().map(|z| self * z).collect(),
@@ -29,17 +33,54 @@ impl Mul<QMatrix> for f64 {
         complex!(self, 0.0) * rhs
     }
 }
-impl QMatrix {
+/// Prints one bracketed row per line with the entries of each column right-aligned.
+/// The precision applies to every entry, as for `Complex`.
+impl<T: Real> fmt::Display for QMatrix<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(DEFAULT_PRECISION);
+        let entries: Vec<String> = self
+            .data
+            .iter()
+            .map(|z| format!("{:.*}", precision, z))
+            .collect();
+        let widths: Vec<usize> = (0..self.dims)
+            .map(|col| {
+                (0..self.dims)
+                    .map(|row| entries[row * self.dims + col].chars().count())
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+        for row in 0..self.dims {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            let cells: Vec<String> = (0..self.dims)
+                .map(|col| format!("{:>1$}", entries[row * self.dims + col], widths[col]))
+                .collect();
+            write!(f, "[{}]", cells.join("  "))?;
+        }
+        Ok(())
+    }
+}
+impl<T: Real> QMatrix<T> {
     pub fn dims(&self) -> usize {
         self.dims
     }
-    pub fn from_data(data: Vec<Complex>) -> QMatrix {
+    pub fn identity(dims: usize) -> QMatrix<T> {
+        let mut data = vec![Complex::zero(); dims.pow(2)];
+        for i in 0..dims {
+            data[i * dims + i] = Complex::one();
+        }
+        QMatrix { dims, data }
+    }
+    pub fn from_data(data: Vec<Complex<T>>) -> QMatrix<T> {
         assert!(is_square_number(data.len()));
         let dims = (data.len() as f32).sqrt() as usize;
         assert!(dims.is_power_of_two());
         QMatrix { dims, data }
     }
-    pub fn from_vecs(data: Vec<Vec<Complex>>) -> QMatrix {
+    pub fn from_vecs(data: Vec<Vec<Complex<T>>>) -> QMatrix<T> {
         let rows = data.len();
         // QMatrix must be square
         assert!(data.iter().all(|row| row.len() == rows));
@@ -48,15 +89,22 @@ impl QMatrix {
             data: data.into_iter().flatten().collect(),
         }
     }
+    /// Converts the entries to another precision.
+    pub fn cast<U: Real>(&self) -> QMatrix<U> {
+        QMatrix {
+            dims: self.dims,
+            data: self.data.iter().map(|z| z.cast()).collect(),
+        }
+    }
     // Now row major
-    pub fn mul(&self, lhs: &QMatrix) -> QMatrix {
+    pub fn mul(&self, lhs: &QMatrix<T>) -> QMatrix<T> {
         assert_eq!(self.dims, lhs.dims);
 
         let dims = self.dims;
-        let mut matrix_data = vec![Complex::default(); dims.pow(2)];
+        let mut matrix_data = vec![Complex::zero(); dims.pow(2)];
         for row in 0..dims {
             for col in 0..dims {
-                let mut sum = Complex::default();
+                let mut sum = Complex::zero();
                 for ele in 0..dims {
                     sum += lhs.data[row * dims + ele] * self.data[ele * dims + col];
                 }
@@ -69,9 +117,37 @@ impl QMatrix {
             data: matrix_data,
         }
     }
-    pub fn kronecker(&self, lhs: &QMatrix) -> QMatrix {
+    /// The conjugate transpose of the matrix.
+    pub fn adjoint(&self) -> QMatrix<T> {
+        let dims = self.dims;
+        QMatrix {
+            dims,
+            data: (0..dims.pow(2))
+                .map(|i| self.data[(i % dims) * dims + i / dims].conj())
+                .collect(),
+        }
+    }
+    pub fn transpose(&self) -> QMatrix<T> {
+        let dims = self.dims;
+        QMatrix {
+            dims,
+            data: (0..dims.pow(2))
+                .map(|i| self.data[(i % dims) * dims + i / dims])
+                .collect(),
+        }
+    }
+    /// Element-wise comparison with an absolute tolerance.
+    pub fn approx_eq(&self, other: &QMatrix<T>, tolerance: f64) -> bool {
+        self.dims == other.dims
+            && self
+                .data
+                .iter()
+                .zip(other.data.iter())
+                .all(|(&a, &b)| (a - b).mag().to_f64() <= tolerance)
+    }
+    pub fn kronecker(&self, lhs: &QMatrix<T>) -> QMatrix<T> {
         let matrix_dims = self.dims * lhs.dims;
-        let mut matrix_data = vec![Complex::default(); matrix_dims.pow(2)];
+        let mut matrix_data = vec![Complex::zero(); matrix_dims.pow(2)];
 
         for lhs_row in 0..lhs.dims {
             for lhs_col in 0..lhs.dims {
@@ -101,6 +177,150 @@ impl QMatrix {
         }
     }
 }
+impl QMatrix {
+    /// Samples a unitary from the Haar measure by orthonormalizing complex Gaussian columns.
+    pub fn haar_random(dims: usize, rng: &mut impl Rng) -> QMatrix {
+        let mut gaussian = || {
+            let (u, v): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+            let radius = (-2.0 * u.ln()).sqrt();
+            complex!(radius * (2.0 * PI * v).cos(), radius * (2.0 * PI * v).sin())
+        };
+        let mut columns: Vec<Vec<Complex>> = Vec::with_capacity(dims);
+        for _ in 0..dims {
+            let mut column: Vec<Complex> = (0..dims).map(|_| gaussian()).collect();
+            for previous in &columns {
+                let overlap: Complex = previous
+                    .iter()
+                    .zip(&column)
+                    .map(|(p, c)| p.conj() * *c)
+                    .sum();
+                for (c, p) in column.iter_mut().zip(previous) {
+                    *c -= overlap * *p;
+                }
+            }
+            let norm = column.iter().map(|c| c.prob()).sum::<f64>().sqrt();
+            columns.push(column.into_iter().map(|c| c / norm).collect());
+        }
+        QMatrix {
+            dims,
+            data: (0..dims * dims)
+                .map(|i| columns[i % dims][i / dims])
+                .collect(),
+        }
+    }
+    /// Calculates the determinant by LU decomposition with partial pivoting.
+    pub fn determinant(&self) -> Complex {
+        let dims = self.dims;
+        let mut data = self.data.clone();
+        let mut det = complex!(1.0, 0.0);
+        for col in 0..dims {
+            let pivot = (col..dims)
+                .max_by(|&i, &j| {
+                    data[i * dims + col]
+                        .mag()
+                        .total_cmp(&data[j * dims + col].mag())
+                })
+                .unwrap();
+            if data[pivot * dims + col].mag() == 0.0 {
+                return Complex::default();
+            }
+            if pivot != col {
+                for k in 0..dims {
+                    data.swap(pivot * dims + k, col * dims + k);
+                }
+                det = -det;
+            }
+            det *= data[col * dims + col];
+            for row in (col + 1)..dims {
+                let factor = data[row * dims + col] / data[col * dims + col];
+                for k in col..dims {
+                    let eliminated = factor * data[col * dims + k];
+                    data[row * dims + k] -= eliminated;
+                }
+            }
+        }
+        det
+    }
+    /// The time evolution e^{-iHt} generated by the Hermitian matrix H = self,
+    /// computed from its eigendecomposition.
+    pub fn expm(&self, t: f64) -> QMatrix {
+        assert!(
+            self.approx_eq(&self.adjoint(), 1e-9),
+            "the generator must be Hermitian"
+        );
+        hermitian_function(self, |value| Complex::cis(-value * t))
+    }
+    /// The eigenvalues of a Hermitian matrix in ascending order, and an orthonormal
+    /// eigenvector for each, found with complex Jacobi rotations.
+    pub fn hermitian_eigen(&self) -> (Vec<f64>, Vec<QState>) {
+        assert!(
+            self.approx_eq(&self.adjoint(), 1e-9),
+            "the matrix must be Hermitian"
+        );
+        let (values, vectors) = hermitian_eigen(self);
+        (values, vectors.columns())
+    }
+    /// The eigenvalues of a unitary matrix, which lie on the unit circle, ordered by
+    /// their phase in (-π, π], and an orthonormal eigenvector for each. They are read
+    /// off the Schur decomposition, which is diagonal for unitary matrices.
+    pub fn unitary_eigen(&self) -> (Vec<Complex>, Vec<QState>) {
+        assert!(
+            self.mul(&self.adjoint())
+                .approx_eq(&QMatrix::identity(self.dims), 1e-9),
+            "the matrix must be unitary"
+        );
+        let (q, t) = complex_schur(self);
+        let vectors = q.columns();
+        let mut order: Vec<usize> = (0..self.dims).collect();
+        let value = |i: usize| t.data[i * self.dims + i];
+        order.sort_by(|&i, &j| value(i).arg().total_cmp(&value(j).arg()));
+        (
+            order.iter().map(|&i| value(i)).collect(),
+            order.iter().map(|&i| vectors[i].clone()).collect(),
+        )
+    }
+    /// The Schur decomposition self = Q·T·Q† with Q unitary and T upper triangular,
+    /// returned as (Q, T). The diagonal of T holds the eigenvalues.
+    pub fn schur(&self) -> (QMatrix, QMatrix) {
+        complex_schur(self)
+    }
+    /// The singular value decomposition self = U·Σ·V† with U and V unitary, returned
+    /// as (U, singular values in descending order, V).
+    pub fn svd(&self) -> (QMatrix, Vec<f64>, QMatrix) {
+        let (u, values, v) = complex_svd(&self.data, self.dims, self.dims);
+        (QMatrix::from_data(u), values, QMatrix::from_data(v))
+    }
+    /// The columns of the matrix as states.
+    fn columns(&self) -> Vec<QState> {
+        (0..self.dims)
+            .map(|col| {
+                QState::from_data(
+                    (0..self.dims)
+                        .map(|row| self.data[row * self.dims + col])
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+    /// Checks whether self = e^{iφ}·other for some global phase φ.
+    pub fn equals_up_to_phase(&self, other: &QMatrix, tolerance: f64) -> bool {
+        // The phase is read off the largest element to keep it well conditioned.
+        let Some((&a, &b)) = self
+            .data
+            .iter()
+            .zip(other.data.iter())
+            .max_by(|(a, _), (b, _)| a.mag().total_cmp(&b.mag()))
+        else {
+            return self.dims == other.dims;
+        };
+        if b.mag() <= tolerance {
+            return false;
+        }
+        let phase = a / b;
+        let phase = phase / phase.mag();
+        self.approx_eq(&(phase * other.clone()), tolerance)
+    }
+}
 fn is_square_number(num: usize) -> bool {
     let sqrt = (num as f32).sqrt() as usize;
     sqrt.pow(2) == num
@@ -109,10 +329,128 @@ fn is_square_number(num: usize) -> bool {
 mod tests {
     use super::*;
     use crate::constants::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    /// Checks that every state is an eigenvector of the matrix with the given eigenvalue.
+    fn assert_eigenpairs(matrix: &QMatrix, values: &[Complex], vectors: &[QState]) {
+        for (value, vector) in values.iter().zip(vectors) {
+            let image = vector.apply(matrix);
+            for (a, b) in image.data_slice().iter().zip(vector.data_slice()) {
+                assert!((*a - *value * *b).mag() < 1e-10);
+            }
+            let norm: f64 = vector.data_slice().iter().map(|z| z.prob()).sum();
+            assert!((norm - 1.0).abs() < 1e-10);
+        }
+    }
+    #[test]
+    fn hermitian_eigen_finds_spectra_of_gates() {
+        let cases: [(&QMatrix, Vec<f64>); 3] = [
+            (&X_GATE, vec![-1.0, 1.0]),
+            (&H_GATE, vec![-1.0, 1.0]),
+            (&SWAP_GATE, vec![-1.0, 1.0, 1.0, 1.0]),
+        ];
+        for (gate, expected) in cases {
+            let (values, vectors) = gate.hermitian_eigen();
+            for (value, expected) in values.iter().zip(&expected) {
+                assert!((value - expected).abs() < 1e-10);
+            }
+            let values: Vec<Complex> = values.iter().map(|&v| complex!(v, 0.0)).collect();
+            assert_eigenpairs(gate, &values, &vectors);
+        }
+        // The antisymmetric state is the only eigenvector of SWAP with eigenvalue -1.
+        let (_, vectors) = SWAP_GATE.hermitian_eigen();
+        let singlet = [ZERO, C_IR2, -C_IR2, ZERO];
+        let overlap: Complex = vectors[0]
+            .data_slice()
+            .iter()
+            .zip(singlet)
+            .map(|(a, b)| a.conj() * b)
+            .sum();
+        assert!((overlap.mag() - 1.0).abs() < 1e-10);
+    }
+    #[test]
+    fn unitary_eigen_finds_phases_on_unit_circle() {
+        let (values, vectors) = X_GATE.unitary_eigen();
+        assert!((values[0] - complex!(1.0, 0.0)).mag() < 1e-10);
+        assert!((values[1] - complex!(-1.0, 0.0)).mag() < 1e-10);
+        assert_eigenpairs(&X_GATE, &values, &vectors);
+        let (values, vectors) = SWAP_GATE.unitary_eigen();
+        assert_eigenpairs(&SWAP_GATE, &values, &vectors);
+        let (values, vectors) = T_GATE.mul(&H_GATE).unitary_eigen();
+        assert_eigenpairs(&T_GATE.mul(&H_GATE), &values, &vectors);
+        let mut rng = StdRng::seed_from_u64(47);
+        let unitary = QMatrix::haar_random(8, &mut rng);
+        let (values, vectors) = unitary.unitary_eigen();
+        assert!(values.iter().all(|z| (z.mag() - 1.0).abs() < 1e-10));
+        assert!(values.windows(2).all(|pair| pair[0].arg() <= pair[1].arg()));
+        assert_eigenpairs(&unitary, &values, &vectors);
+    }
+    #[test]
+    fn schur_and_svd_reconstruct_matrix() {
+        let mut rng = StdRng::seed_from_u64(48);
+        let matrix = QMatrix::haar_random(4, &mut rng).kronecker(&H_GATE);
+        let (q, t) = matrix.schur();
+        assert!(q.adjoint().mul(&t).mul(&q).approx_eq(&matrix, 1e-10));
+        let (u, values, v) = matrix.svd();
+        assert!(values.iter().all(|&value| (value - 1.0).abs() < 1e-10));
+        let sigma = QMatrix::from_data(
+            (0..64)
+                .map(|i| match i % 9 {
+                    0 => complex!(values[i / 9], 0.0),
+                    _ => ZERO,
+                })
+                .collect(),
+        );
+        assert!(v.adjoint().mul(&sigma).mul(&u).approx_eq(&matrix, 1e-10));
+        // A rank-one matrix still yields a unitary U.
+        let projector = QMatrix::from_data(vec![ONE, ZERO, ZERO, ZERO]).kronecker(&I_GATE);
+        let (u, values, _) = projector.svd();
+        assert!((values[0] - 1.0).abs() < 1e-10 && values[2].abs() < 1e-10);
+        assert!(u.mul(&u.adjoint()).approx_eq(&QMatrix::identity(4), 1e-10));
+    }
+    #[test]
+    fn expm_of_pauli_generators_gives_rotations() {
+        let theta = 0.9;
+        let rz = (0.5 * Z_GATE.clone()).expm(theta);
+        let expected = QMatrix::from_data(vec![
+            Complex::cis(-theta / 2.0),
+            ZERO,
+            ZERO,
+            Complex::cis(theta / 2.0),
+        ]);
+        assert!(rz.approx_eq(&expected, 1e-12));
+        let (sin, cos) = theta.sin_cos();
+        let rx = X_GATE.expm(theta);
+        let expected = QMatrix::from_data(vec![
+            complex!(cos, 0.0),
+            complex!(0.0, -sin),
+            complex!(0.0, -sin),
+            complex!(cos, 0.0),
+        ]);
+        assert!(rx.approx_eq(&expected, 1e-12));
+    }
+    #[test]
+    fn expm_of_hermitian_matrix_is_unitary_group() {
+        let mut rng = StdRng::seed_from_u64(46);
+        let u = QMatrix::haar_random(4, &mut rng);
+        let h = u.kronecker(&I_GATE);
+        let hermitian = QMatrix::from_data(
+            h.data_slice()
+                .iter()
+                .zip(h.adjoint().data_slice())
+                .map(|(&a, &b)| a + b)
+                .collect(),
+        );
+        let evolution = hermitian.expm(0.4);
+        let product = evolution.mul(&evolution.adjoint());
+        assert!(product.approx_eq(&QMatrix::identity(8), 1e-10));
+        assert!(evolution
+            .mul(&hermitian.expm(0.6))
+            .approx_eq(&hermitian.expm(1.0), 1e-10));
+    }
     #[test]
     #[should_panic]
     fn creating_qmatrix_from_data_with_non_square_dims_panics() {
-        let data = vec![Complex::default(); 9999];
+        let data: Vec<Complex> = vec![Complex::default(); 9999];
         let _ = QMatrix::from_data(data);
     }
 
@@ -151,6 +489,40 @@ mod tests {
         assert_eq!(result, expected_result);
     }
     #[test]
+    fn adjoint_of_pauli_y_is_pauli_y() {
+        assert_eq!(Y_GATE.adjoint(), *Y_GATE);
+        assert!(T_GATE
+            .mul(&T_GATE.adjoint())
+            .approx_eq(&QMatrix::identity(2), 1e-12));
+    }
+    #[test]
+    fn haar_random_matrices_are_unitary() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let unitary = QMatrix::haar_random(8, &mut rng);
+        assert!(unitary
+            .mul(&unitary.adjoint())
+            .approx_eq(&QMatrix::identity(8), 1e-12));
+    }
+    #[test]
+    fn determinant_of_swap_is_minus_one() {
+        assert!(SWAP_GATE.determinant().equals(-ONE));
+        assert!(CNOT_GATE.kronecker(&S_GATE).determinant().equals(ONE));
+        let matrix = QMatrix::from_data(vec![
+            complex!(1.0, 1.0),
+            complex!(2.0, 0.0),
+            complex!(0.0, 3.0),
+            complex!(4.0, -1.0),
+        ]);
+        assert!((matrix.determinant() + complex!(-5.0, 3.0)).mag() < 1e-12);
+    }
+    #[test]
+    fn equals_up_to_phase_ignores_global_phase_only() {
+        let phased = I * Z_GATE.clone();
+        assert!(phased.equals_up_to_phase(&Z_GATE, 1e-12));
+        assert!(!phased.approx_eq(&Z_GATE, 1e-12));
+        assert!(!X_GATE.equals_up_to_phase(&Z_GATE, 1e-12));
+    }
+    #[test]
     fn kronecker_product_of_pauli_y_cnot_gives_correct_output() {
         let result = CNOT_GATE.kronecker(&Y_GATE);
         #[rustfmt::skip]
@@ -209,4 +581,13 @@ mod tests {
         dbg!(&result, &expected_result);
         assert!(result.equals(&expected_result));
     }
+    #[test]
+    fn matrix_display_aligns_columns() {
+        let matrix = QMatrix::from_vecs(vec![
+            vec![complex!(1.0, 0.0), complex!(0.5, -0.5)],
+            vec![complex!(0.0, 1.0), complex!(-0.25, 0.0)],
+        ]);
+        assert_eq!(matrix.to_string(), "[ 1  0.5-0.5i]\n[1i     -0.25]");
+        assert_eq!(format!("{:.1}", *H_GATE), "[0.7   0.7]\n[0.7  -0.7]");
+    }
 }
@@ -0,0 +1,330 @@
+//! Classical minimizers for variational algorithms.
+use std::cell::Cell;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// A function to minimize over real parameters.
+pub trait Objective {
+    fn value(&mut self, parameters: &[f64]) -> f64;
+    /// The gradient of the value. Only gradient-based minimizers call it.
+    fn gradient(&mut self, parameters: &[f64]) -> Vec<f64>;
+}
+/// A classical minimization method.
+pub trait Minimizer {
+    fn minimize(&self, objective: &mut dyn Objective, initial: &[f64]) -> Minimum;
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimum {
+    pub parameters: Vec<f64>,
+    pub value: f64,
+    /// The number of times the objective's value or gradient was evaluated.
+    pub evaluations: usize,
+}
+/// The downhill simplex method of Nelder and Mead, which needs only values.
+#[derive(Debug, Clone)]
+pub struct NelderMead {
+    max_evaluations: usize,
+    initial_step: f64,
+    tolerance: f64,
+}
+/// Simultaneous perturbation stochastic approximation (Spall, 1992), which estimates
+/// the gradient from two values along a random direction, so noisy values suffice.
+#[derive(Debug, Clone)]
+pub struct Spsa {
+    iterations: usize,
+    learning_rate: f64,
+    perturbation: f64,
+    seed: u64,
+}
+/// Gradient descent with the Adam update rule (Kingma and Ba, 2015).
+#[derive(Debug, Clone)]
+pub struct Adam {
+    iterations: usize,
+    learning_rate: f64,
+    beta_1: f64,
+    beta_2: f64,
+    tolerance: f64,
+}
+impl Default for NelderMead {
+    fn default() -> Self {
+        NelderMead::new()
+    }
+}
+impl NelderMead {
+    pub fn new() -> NelderMead {
+        NelderMead {
+            max_evaluations: 10_000,
+            initial_step: 0.5,
+            tolerance: 1e-10,
+        }
+    }
+    pub fn max_evaluations(mut self, max_evaluations: usize) -> Self {
+        self.max_evaluations = max_evaluations;
+        self
+    }
+    /// The distance from the initial point to the other vertices of the first simplex.
+    pub fn initial_step(mut self, initial_step: f64) -> Self {
+        self.initial_step = initial_step;
+        self
+    }
+    /// Stops once the values at the vertices of the simplex differ by less than this.
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+impl Minimizer for NelderMead {
+    fn minimize(&self, objective: &mut dyn Objective, initial: &[f64]) -> Minimum {
+        let n = initial.len();
+        let evaluations = Cell::new(0);
+        let mut evaluate = |point: &[f64]| {
+            evaluations.set(evaluations.get() + 1);
+            objective.value(point)
+        };
+        let mut simplex: Vec<(Vec<f64>, f64)> = (0..=n)
+            .map(|i| {
+                let mut point = initial.to_vec();
+                if i > 0 {
+                    point[i - 1] += self.initial_step;
+                }
+                let value = evaluate(&point);
+                (point, value)
+            })
+            .collect();
+        // A point on the line from the centroid c of the best n vertices through the
+        // worst vertex w, at c + t·(w − c).
+        let along = |centroid: &[f64], worst: &[f64], t: f64| -> Vec<f64> {
+            centroid
+                .iter()
+                .zip(worst)
+                .map(|(c, w)| c + t * (w - c))
+                .collect()
+        };
+        loop {
+            simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let (best, worst) = (simplex[0].1, simplex[n].1);
+            if worst - best < self.tolerance || evaluations.get() >= self.max_evaluations {
+                break;
+            }
+            let centroid: Vec<f64> = (0..n)
+                .map(|j| simplex[..n].iter().map(|(point, _)| point[j]).sum::<f64>() / n as f64)
+                .collect();
+            let reflected = along(&centroid, &simplex[n].0, -1.0);
+            let reflected_value = evaluate(&reflected);
+            if reflected_value < best {
+                let expanded = along(&centroid, &simplex[n].0, -2.0);
+                let expanded_value = evaluate(&expanded);
+                simplex[n] = match expanded_value < reflected_value {
+                    true => (expanded, expanded_value),
+                    false => (reflected, reflected_value),
+                };
+            } else if reflected_value < simplex[n - 1].1 {
+                simplex[n] = (reflected, reflected_value);
+            } else {
+                let (t, bound) = match reflected_value < worst {
+                    true => (-0.5, reflected_value),
+                    false => (0.5, worst),
+                };
+                let contracted = along(&centroid, &simplex[n].0, t);
+                let contracted_value = evaluate(&contracted);
+                if contracted_value < bound {
+                    simplex[n] = (contracted, contracted_value);
+                } else {
+                    // Shrink every vertex halfway towards the best one.
+                    let best_point = simplex[0].0.clone();
+                    for (point, value) in simplex.iter_mut().skip(1) {
+                        *point = along(&best_point, point, 0.5);
+                        *value = evaluate(point);
+                    }
+                }
+            }
+        }
+        let (parameters, value) = simplex.swap_remove(0);
+        Minimum {
+            parameters,
+            value,
+            evaluations: evaluations.get(),
+        }
+    }
+}
+impl Default for Spsa {
+    fn default() -> Self {
+        Spsa::new()
+    }
+}
+impl Spsa {
+    pub fn new() -> Spsa {
+        Spsa {
+            iterations: 1000,
+            learning_rate: 0.2,
+            perturbation: 0.1,
+            seed: 0,
+        }
+    }
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    /// The initial step size a, which decays as a/(k + 1 + A)^0.602 with A a tenth of
+    /// the iterations.
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+    /// The initial perturbation size c, which decays as c/(k + 1)^0.101.
+    pub fn perturbation(mut self, perturbation: f64) -> Self {
+        self.perturbation = perturbation;
+        self
+    }
+    /// Seeds the random perturbation directions.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+impl Minimizer for Spsa {
+    fn minimize(&self, objective: &mut dyn Objective, initial: &[f64]) -> Minimum {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let stability = self.iterations as f64 / 10.0;
+        let mut parameters = initial.to_vec();
+        for k in 0..self.iterations {
+            let step = self.learning_rate / (k as f64 + 1.0 + stability).powf(0.602);
+            let size = self.perturbation / (k as f64 + 1.0).powf(0.101);
+            let direction: Vec<f64> = parameters
+                .iter()
+                .map(|_| if rng.gen_bool(0.5) { 1.0 } else { -1.0 })
+                .collect();
+            let shifted = |sign: f64| -> Vec<f64> {
+                parameters
+                    .iter()
+                    .zip(&direction)
+                    .map(|(x, d)| x + sign * size * d)
+                    .collect()
+            };
+            let (plus, minus) = (shifted(1.0), shifted(-1.0));
+            let slope = (objective.value(&plus) - objective.value(&minus)) / (2.0 * size);
+            for (x, d) in parameters.iter_mut().zip(&direction) {
+                *x -= step * slope * d;
+            }
+        }
+        let value = objective.value(&parameters);
+        Minimum {
+            parameters,
+            value,
+            evaluations: 2 * self.iterations + 1,
+        }
+    }
+}
+impl Default for Adam {
+    fn default() -> Self {
+        Adam::new()
+    }
+}
+impl Adam {
+    pub fn new() -> Adam {
+        Adam {
+            iterations: 1000,
+            learning_rate: 0.05,
+            beta_1: 0.9,
+            beta_2: 0.999,
+            tolerance: 1e-8,
+        }
+    }
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+    /// The decay rates of the running averages of the gradient and its square.
+    pub fn betas(mut self, beta_1: f64, beta_2: f64) -> Self {
+        self.beta_1 = beta_1;
+        self.beta_2 = beta_2;
+        self
+    }
+    /// Stops once the gradient is shorter than this.
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+impl Minimizer for Adam {
+    fn minimize(&self, objective: &mut dyn Objective, initial: &[f64]) -> Minimum {
+        let mut parameters = initial.to_vec();
+        let mut first_moment = vec![0.0; parameters.len()];
+        let mut second_moment = vec![0.0; parameters.len()];
+        let mut evaluations = 0;
+        for t in 1..=self.iterations {
+            let gradient = objective.gradient(&parameters);
+            evaluations += 1;
+            if gradient.iter().map(|g| g * g).sum::<f64>().sqrt() < self.tolerance {
+                break;
+            }
+            let correction_1 = 1.0 - self.beta_1.powi(t as i32);
+            let correction_2 = 1.0 - self.beta_2.powi(t as i32);
+            for (j, g) in gradient.into_iter().enumerate() {
+                first_moment[j] = self.beta_1 * first_moment[j] + (1.0 - self.beta_1) * g;
+                second_moment[j] = self.beta_2 * second_moment[j] + (1.0 - self.beta_2) * g * g;
+                let (m, v) = (
+                    first_moment[j] / correction_1,
+                    second_moment[j] / correction_2,
+                );
+                parameters[j] -= self.learning_rate * m / (v.sqrt() + 1e-8);
+            }
+        }
+        let value = objective.value(&parameters);
+        Minimum {
+            parameters,
+            value,
+            evaluations: evaluations + 1,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Rosenbrock function, with its minimum of 0 at (1, 1).
+    struct Rosenbrock;
+    impl Objective for Rosenbrock {
+        fn value(&mut self, p: &[f64]) -> f64 {
+            (1.0 - p[0]).powi(2) + 100.0 * (p[1] - p[0] * p[0]).powi(2)
+        }
+        fn gradient(&mut self, p: &[f64]) -> Vec<f64> {
+            vec![
+                -2.0 * (1.0 - p[0]) - 400.0 * p[0] * (p[1] - p[0] * p[0]),
+                200.0 * (p[1] - p[0] * p[0]),
+            ]
+        }
+    }
+    /// A quadratic bowl centred on (1, -2, 0.5).
+    struct Bowl;
+    impl Objective for Bowl {
+        fn value(&mut self, p: &[f64]) -> f64 {
+            (p[0] - 1.0).powi(2) + 2.0 * (p[1] + 2.0).powi(2) + 3.0 * (p[2] - 0.5).powi(2)
+        }
+        fn gradient(&mut self, p: &[f64]) -> Vec<f64> {
+            vec![2.0 * (p[0] - 1.0), 4.0 * (p[1] + 2.0), 6.0 * (p[2] - 0.5)]
+        }
+    }
+    #[test]
+    fn nelder_mead_finds_rosenbrock_minimum() {
+        let minimum = NelderMead::new().minimize(&mut Rosenbrock, &[-1.2, 1.0]);
+        assert!(minimum.value < 1e-8);
+        assert!((minimum.parameters[0] - 1.0).abs() < 1e-3);
+        assert!((minimum.parameters[1] - 1.0).abs() < 1e-3);
+    }
+    #[test]
+    fn spsa_and_adam_find_bowl_minimum() {
+        let initial = [0.0, 0.0, 0.0];
+        for minimum in [
+            Spsa::new().seed(1).minimize(&mut Bowl, &initial),
+            Adam::new().minimize(&mut Bowl, &initial),
+        ] {
+            assert!(minimum.value < 1e-4, "{minimum:?}");
+        }
+    }
+}
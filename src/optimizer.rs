@@ -0,0 +1,341 @@
+use crate::circuit::{Circuit, Gate, Instruction};
+use crate::decomposition::{euler_angles, EulerBasis};
+use crate::matrix::QMatrix;
+
+const TOLERANCE: f64 = 1e-10;
+
+/// A circuit-to-circuit rewrite that preserves the circuit's unitary.
+pub trait Pass {
+    fn run(&self, circuit: &Circuit) -> Circuit;
+}
+/// Removes adjacent pairs of mutually inverse gates, such as `H H` or `CNOT CNOT`.
+pub struct CancelInversePairs;
+/// Fuses runs of consecutive single-qubit gates on a qubit into one unitary gate.
+pub struct MergeSingleQubitGates;
+/// Removes gates that act as the identity.
+pub struct RemoveIdentities;
+/// Moves diagonal single-qubit gates as early as possible, past gates they commute with,
+/// such as CNOTs they control. This lets them meet other gates on the same qubit.
+pub struct CommuteDiagonalGates;
+/// Rewrites fused single-qubit unitaries as rotations in an Euler basis, dropping the global phase.
+pub struct EmitEulerBasis(pub EulerBasis);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizationReport {
+    pub gates_before: usize,
+    pub gates_after: usize,
+    pub depth_before: usize,
+    pub depth_after: usize,
+}
+/// Runs a sequence of passes repeatedly until the gate count stops decreasing.
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    max_iterations: usize,
+}
+impl Default for PassManager {
+    fn default() -> Self {
+        let mut manager = PassManager::new();
+        manager
+            .add(CommuteDiagonalGates)
+            .add(CancelInversePairs)
+            .add(MergeSingleQubitGates)
+            .add(RemoveIdentities);
+        manager
+    }
+}
+impl PassManager {
+    pub fn new() -> PassManager {
+        PassManager {
+            passes: Vec::new(),
+            max_iterations: 10,
+        }
+    }
+    pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+    pub fn max_iterations(&mut self, max_iterations: usize) -> &mut Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+    pub fn run(&self, circuit: &Circuit) -> (Circuit, OptimizationReport) {
+        let mut optimized = circuit.clone();
+        for _ in 0..self.max_iterations {
+            let before = optimized.gate_count();
+            optimized = self
+                .passes
+                .iter()
+                .fold(optimized, |circuit, pass| pass.run(&circuit));
+            if optimized.gate_count() >= before {
+                break;
+            }
+        }
+        let report = OptimizationReport {
+            gates_before: circuit.gate_count(),
+            gates_after: optimized.gate_count(),
+            depth_before: circuit.depth(),
+            depth_after: optimized.depth(),
+        };
+        (optimized, report)
+    }
+}
+impl Pass for CancelInversePairs {
+    fn run(&self, circuit: &Circuit) -> Circuit {
+        let mut kept: Vec<Option<Instruction>> = Vec::new();
+        // For every qubit, the indices into `kept` of the live instructions touching it.
+        let mut stacks: Vec<Vec<usize>> = vec![Vec::new(); circuit.num_qubits()];
+        for instruction in circuit.instructions() {
+            let Instruction::Gate { gate, qubits } = instruction else {
+                let blocked: Vec<usize> = match instruction {
+                    Instruction::Barrier => (0..circuit.num_qubits()).collect(),
+                    _ => instruction.qubits().to_vec(),
+                };
+                for q in blocked {
+                    stacks[q].push(kept.len());
+                }
+                kept.push(Some(instruction.clone()));
+                continue;
+            };
+            let previous = stacks[qubits[0]].last().copied();
+            let cancels = previous.is_some_and(|index| {
+                let Some(Instruction::Gate {
+                    gate: previous_gate,
+                    qubits: previous_qubits,
+                }) = &kept[index]
+                else {
+                    return false;
+                };
+                qubits.iter().all(|&q| stacks[q].last() == Some(&index))
+                    && same_qubits(gate, qubits, previous_qubits)
+                    && is_identity(&previous_gate.matrix().mul(&gate.matrix()))
+            });
+            if cancels {
+                kept[previous.unwrap()] = None;
+                for &q in qubits {
+                    stacks[q].pop();
+                }
+            } else {
+                for &q in qubits {
+                    stacks[q].push(kept.len());
+                }
+                kept.push(Some(instruction.clone()));
+            }
+        }
+        rebuild(circuit, kept.into_iter().flatten())
+    }
+}
+impl Pass for MergeSingleQubitGates {
+    fn run(&self, circuit: &Circuit) -> Circuit {
+        let mut kept: Vec<Option<Instruction>> = Vec::new();
+        // The index in `kept` of the single-qubit gate that currently ends each qubit's wire.
+        let mut open: Vec<Option<usize>> = vec![None; circuit.num_qubits()];
+        for instruction in circuit.instructions() {
+            match instruction {
+                Instruction::Gate { gate, qubits } if qubits.len() == 1 => {
+                    let qubit = qubits[0];
+                    match open[qubit] {
+                        Some(index) => {
+                            let Some(Instruction::Gate { gate: previous, .. }) = &kept[index]
+                            else {
+                                unreachable!()
+                            };
+                            let fused = previous.matrix().mul(&gate.matrix());
+                            kept[index] = Some(Instruction::Gate {
+                                gate: Gate::Unitary(fused),
+                                qubits: qubits.clone(),
+                            });
+                        }
+                        None => {
+                            open[qubit] = Some(kept.len());
+                            kept.push(Some(instruction.clone()));
+                        }
+                    }
+                }
+                Instruction::Gate { .. }
+                | Instruction::Measure { .. }
+                | Instruction::Conditional { .. } => {
+                    for &q in instruction.qubits() {
+                        open[q] = None;
+                    }
+                    kept.push(Some(instruction.clone()));
+                }
+                Instruction::Barrier => {
+                    open.iter_mut().for_each(|index| *index = None);
+                    kept.push(Some(instruction.clone()));
+                }
+            }
+        }
+        rebuild(circuit, kept.into_iter().flatten())
+    }
+}
+impl Pass for RemoveIdentities {
+    fn run(&self, circuit: &Circuit) -> Circuit {
+        rebuild(
+            circuit,
+            circuit
+                .instructions()
+                .iter()
+                .filter(|instruction| {
+                    !matches!(instruction, Instruction::Gate { gate, .. } if is_identity(&gate.matrix()))
+                })
+                .cloned(),
+        )
+    }
+}
+impl Pass for CommuteDiagonalGates {
+    fn run(&self, circuit: &Circuit) -> Circuit {
+        let mut output: Vec<Instruction> = Vec::new();
+        for instruction in circuit.instructions() {
+            let mut position = output.len();
+            if let Instruction::Gate { gate, qubits } = instruction {
+                if qubits.len() == 1 && gate.is_diagonal() {
+                    while position > 0 && commutes_with_diagonal(&output[position - 1], qubits[0]) {
+                        position -= 1;
+                    }
+                }
+            }
+            output.insert(position, instruction.clone());
+        }
+        rebuild(circuit, output.into_iter())
+    }
+}
+impl Pass for EmitEulerBasis {
+    fn run(&self, circuit: &Circuit) -> Circuit {
+        let mut rebuilt = Circuit::with_clbits(circuit.num_qubits(), circuit.num_clbits());
+        for instruction in circuit.instructions() {
+            match instruction {
+                Instruction::Gate {
+                    gate: Gate::Unitary(matrix),
+                    qubits,
+                } if qubits.len() == 1 => {
+                    for gate in euler_angles(matrix, self.0).gates() {
+                        rebuilt.add(gate, qubits);
+                    }
+                }
+                _ => {
+                    rebuilt.push(instruction.clone());
+                }
+            }
+        }
+        rebuilt
+    }
+}
+/// Whether a diagonal gate on `qubit` can be moved across the instruction.
+/// Stops at other single-qubit gates on the same qubit so they can be merged.
+fn commutes_with_diagonal(instruction: &Instruction, qubit: usize) -> bool {
+    match instruction {
+        Instruction::Barrier => false,
+        _ if !instruction.qubits().contains(&qubit) => true,
+        Instruction::Measure { .. } | Instruction::Conditional { .. } => false,
+        Instruction::Gate { qubits, .. } if qubits.len() == 1 => false,
+        Instruction::Gate { gate, qubits } => {
+            gate.is_diagonal() || (*gate == Gate::Cnot && qubits[0] == qubit)
+        }
+    }
+}
+fn same_qubits(gate: &Gate, qubits: &[usize], other: &[usize]) -> bool {
+    qubits == other
+        || (gate.is_symmetric()
+            && qubits.len() == other.len()
+            && qubits.iter().rev().eq(other.iter()))
+}
+fn is_identity(matrix: &QMatrix) -> bool {
+    matrix.approx_eq(&QMatrix::identity(matrix.dims()), TOLERANCE)
+}
+fn rebuild(circuit: &Circuit, instructions: impl Iterator<Item = Instruction>) -> Circuit {
+    let mut rebuilt = Circuit::with_clbits(circuit.num_qubits(), circuit.num_clbits());
+    for instruction in instructions {
+        rebuilt.push(instruction);
+    }
+    rebuilt
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelling_nested_inverse_pairs_leaves_empty_circuit() {
+        let mut circuit = Circuit::new(2);
+        circuit.h(0).x(1).cnot(0, 1).cnot(0, 1).x(1).h(0);
+        let optimized = CancelInversePairs.run(&circuit);
+        assert_eq!(optimized.gate_count(), 0);
+    }
+    #[test]
+    fn inverse_pairs_separated_by_a_barrier_are_kept() {
+        let mut circuit = Circuit::new(1);
+        circuit.h(0).barrier().h(0);
+        let optimized = CancelInversePairs.run(&circuit);
+        assert_eq!(optimized.gate_count(), 2);
+    }
+    #[test]
+    fn merging_single_qubit_runs_preserves_unitary() {
+        let mut circuit = Circuit::new(2);
+        circuit.h(0).t(0).rx(0.4, 0).cnot(0, 1).rz(1.1, 1).h(1);
+        let optimized = MergeSingleQubitGates.run(&circuit);
+        assert_eq!(optimized.gate_count(), 3);
+        assert!(optimized.unitary().approx_eq(&circuit.unitary(), 1e-12));
+    }
+    #[test]
+    fn diagonal_gates_commute_through_cnot_controls_and_cancel() {
+        let mut circuit = Circuit::new(2);
+        circuit
+            .add(Gate::T, &[0])
+            .cnot(0, 1)
+            .add(Gate::Tdg, &[0])
+            .cnot(0, 1);
+        let (optimized, report) = PassManager::default().run(&circuit);
+        assert_eq!(optimized.gate_count(), 0);
+        assert_eq!(report.gates_before, 4);
+        assert_eq!(report.depth_after, 0);
+    }
+    #[test]
+    fn fused_gates_are_emitted_as_euler_rotations() {
+        let mut circuit = Circuit::new(1);
+        circuit.h(0).t(0).add(Gate::Sx, &[0]);
+        let mut manager = PassManager::new();
+        manager
+            .add(MergeSingleQubitGates)
+            .add(EmitEulerBasis(EulerBasis::ZYZ));
+        let (optimized, _) = manager.run(&circuit);
+        assert!(optimized.instructions().iter().all(|instruction| matches!(
+            instruction,
+            Instruction::Gate {
+                gate: Gate::Rz(_) | Gate::Ry(_),
+                ..
+            }
+        )));
+        assert!(optimized
+            .unitary()
+            .equals_up_to_phase(&circuit.unitary(), 1e-10));
+    }
+    #[test]
+    fn diagonal_gates_do_not_commute_through_cnot_targets() {
+        let mut circuit = Circuit::new(2);
+        circuit.z(1).cnot(0, 1).z(1);
+        let optimized = CommuteDiagonalGates.run(&circuit);
+        assert_eq!(optimized, circuit);
+    }
+    #[test]
+    fn pass_manager_reduces_gate_count_and_depth_and_preserves_unitary() {
+        let mut circuit = Circuit::new(3);
+        circuit
+            .h(0)
+            .h(0)
+            .x(1)
+            .rz(0.3, 2)
+            .cnot(1, 2)
+            .rz(-0.3, 2)
+            .add(Gate::S, &[1])
+            .cnot(1, 2)
+            .add(Gate::Sdg, &[1])
+            .add(Gate::I, &[0])
+            .t(2)
+            .h(2);
+        let (optimized, report) = PassManager::default().run(&circuit);
+        assert!(optimized.unitary().approx_eq(&circuit.unitary(), 1e-10));
+        assert_eq!(report.gates_before, 12);
+        assert_eq!(report.gates_after, optimized.gate_count());
+        assert!(report.gates_after < report.gates_before);
+        assert!(report.depth_after < report.depth_before);
+    }
+}
@@ -0,0 +1,263 @@
+//! Hamiltonians written as weighted sums of Pauli products, and their Trotterized
+//! time evolution.
+use crate::circuit::{Circuit, Gate};
+use crate::complex::Complex;
+use crate::constants::{I_GATE, X_GATE, Y_GATE, Z_GATE};
+use crate::matrix::QMatrix;
+use crate::{QState, QuantumVec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+impl Pauli {
+    pub fn matrix(&self) -> QMatrix {
+        match self {
+            Pauli::I => I_GATE.clone(),
+            Pauli::X => X_GATE.clone(),
+            Pauli::Y => Y_GATE.clone(),
+            Pauli::Z => Z_GATE.clone(),
+        }
+    }
+}
+/// A real multiple of a product of Pauli operators on distinct qubits. Qubits that
+/// are not listed carry the identity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PauliTerm {
+    pub coefficient: f64,
+    pub paulis: Vec<(usize, Pauli)>,
+}
+impl PauliTerm {
+    /// The Pauli acting on `qubit`.
+    pub fn pauli_on(&self, qubit: usize) -> Pauli {
+        self.paulis
+            .iter()
+            .find(|(q, _)| *q == qubit)
+            .map_or(Pauli::I, |&(_, pauli)| pauli)
+    }
+    /// The qubits the term acts on with X, Y or Z, in ascending order.
+    pub fn support(&self) -> Vec<usize> {
+        let mut support: Vec<usize> = self
+            .paulis
+            .iter()
+            .filter(|(_, pauli)| *pauli != Pauli::I)
+            .map(|&(q, _)| q)
+            .collect();
+        support.sort_unstable();
+        support
+    }
+}
+/// How a Trotter step splits e^{-iHΔt} into exponentials of single terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrotterOrder {
+    /// e^{-iH₁Δt}···e^{-iHₘΔt}, with an error of O(Δt²) per step.
+    First,
+    /// The symmetric product e^{-iH₁Δt/2}···e^{-iHₘΔt/2}·e^{-iHₘΔt/2}···e^{-iH₁Δt/2},
+    /// with an error of O(Δt³) per step.
+    Second,
+}
+/// A Hermitian operator on a fixed number of qubits, written as a sum of Pauli terms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PauliSum {
+    num_qubits: usize,
+    terms: Vec<PauliTerm>,
+}
+impl PauliSum {
+    pub fn new(num_qubits: usize) -> PauliSum {
+        PauliSum {
+            num_qubits,
+            terms: Vec::new(),
+        }
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+    pub fn terms(&self) -> &[PauliTerm] {
+        &self.terms
+    }
+    /// Adds `coefficient` times the product of the given Paulis.
+    pub fn add_term(&mut self, coefficient: f64, paulis: &[(usize, Pauli)]) -> &mut Self {
+        assert!(paulis.iter().all(|&(q, _)| q < self.num_qubits));
+        let mask: usize = paulis.iter().map(|&(q, _)| 1 << q).sum();
+        assert_eq!(
+            mask.count_ones() as usize,
+            paulis.len(),
+            "qubits must be distinct"
+        );
+        self.terms.push(PauliTerm {
+            coefficient,
+            paulis: paulis.to_vec(),
+        });
+        self
+    }
+    /// The dense matrix of the operator, where qubit i is bit i of the basis index.
+    pub fn matrix(&self) -> QMatrix {
+        let dims = 1 << self.num_qubits;
+        let mut data = vec![Complex::zero(); dims * dims];
+        for term in &self.terms {
+            let product = (1..self.num_qubits).fold(term.pauli_on(0).matrix(), |product, q| {
+                product.kronecker(&term.pauli_on(q).matrix())
+            });
+            for (entry, &value) in data.iter_mut().zip(product.data_slice()) {
+                *entry += term.coefficient * value;
+            }
+        }
+        QMatrix::from_data(data)
+    }
+    /// The operator applied to a state, H|ψ⟩, which is in general not normalized.
+    pub fn apply(&self, state: &QState) -> QState {
+        assert_eq!(state.num_qubits(), self.num_qubits);
+        let mut result = vec![Complex::zero(); state.data_slice().len()];
+        for term in &self.terms {
+            let image = term
+                .paulis
+                .iter()
+                .fold(state.clone(), |image, &(q, pauli)| {
+                    image.apply_on(&pauli.matrix(), &[q])
+                });
+            for (entry, &value) in result.iter_mut().zip(image.data_slice()) {
+                *entry += term.coefficient * value;
+            }
+        }
+        QState::from_data(result)
+    }
+    /// The expectation value ⟨ψ|H|ψ⟩.
+    pub fn expectation(&self, state: &QState) -> f64 {
+        state
+            .data_slice()
+            .iter()
+            .zip(self.apply(state).data_slice())
+            .map(|(a, b)| (a.conj() * *b).re)
+            .sum()
+    }
+    /// A circuit approximating e^{-iHt} with `steps` Trotter steps. Each term becomes
+    /// a basis change onto Z, a CNOT ladder computing the parity of its support, an
+    /// RZ rotation and the ladder and basis change undone. Identity terms only
+    /// contribute a global phase and are left out.
+    pub fn trotter_circuit(&self, time: f64, steps: usize, order: TrotterOrder) -> Circuit {
+        assert!(steps > 0);
+        let dt = time / steps as f64;
+        let mut circuit = Circuit::new(self.num_qubits);
+        for _ in 0..steps {
+            match order {
+                TrotterOrder::First => {
+                    for term in &self.terms {
+                        add_term_evolution(&mut circuit, term, dt);
+                    }
+                }
+                TrotterOrder::Second => {
+                    for term in self.terms.iter().chain(self.terms.iter().rev()) {
+                        add_term_evolution(&mut circuit, term, dt / 2.0);
+                    }
+                }
+            }
+        }
+        circuit
+    }
+}
+/// Appends e^{-i·c·P·t} for the term c·P.
+fn add_term_evolution(circuit: &mut Circuit, term: &PauliTerm, time: f64) {
+    let support = term.support();
+    let Some(&last) = support.last() else {
+        return;
+    };
+    // Gates that rotate each Pauli onto Z, and their inverses.
+    let basis_change = |q: usize| match term.pauli_on(q) {
+        Pauli::X => vec![Gate::H],
+        Pauli::Y => vec![Gate::Sdg, Gate::H],
+        _ => vec![],
+    };
+    for &q in &support {
+        for gate in basis_change(q) {
+            circuit.add(gate, &[q]);
+        }
+    }
+    for pair in support.windows(2) {
+        circuit.cnot(pair[0], pair[1]);
+    }
+    circuit.rz(2.0 * term.coefficient * time, last);
+    for pair in support.windows(2).rev() {
+        circuit.cnot(pair[0], pair[1]);
+    }
+    for &q in &support {
+        for gate in basis_change(q).iter().rev() {
+            circuit.add(gate.inverse(), &[q]);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A transverse-field Ising chain with an extra Y coupling, so that no two
+    /// neighbouring terms commute.
+    fn hamiltonian() -> PauliSum {
+        let mut hamiltonian = PauliSum::new(3);
+        hamiltonian
+            .add_term(1.0, &[(0, Pauli::Z), (1, Pauli::Z)])
+            .add_term(0.7, &[(1, Pauli::Z), (2, Pauli::Z)])
+            .add_term(0.9, &[(0, Pauli::X)])
+            .add_term(0.5, &[(1, Pauli::X)])
+            .add_term(1.1, &[(2, Pauli::X)])
+            .add_term(0.4, &[(0, Pauli::Y), (2, Pauli::Y)]);
+        hamiltonian
+    }
+    fn distance(a: &QMatrix, b: &QMatrix) -> f64 {
+        a.data_slice()
+            .iter()
+            .zip(b.data_slice())
+            .map(|(x, y)| (*x - *y).prob())
+            .sum::<f64>()
+            .sqrt()
+    }
+    #[test]
+    fn expectation_matches_dense_matrix() {
+        let hamiltonian = hamiltonian();
+        let state = QState::basis_state(3, 0b101)
+            .apply_on(&crate::constants::H_GATE, &[1])
+            .apply_on(&crate::constants::T_GATE, &[1])
+            .apply_on(&crate::constants::CNOT_GATE, &[1, 2]);
+        let image = state.apply(&hamiltonian.matrix());
+        let expected: f64 = state
+            .data_slice()
+            .iter()
+            .zip(image.data_slice())
+            .map(|(a, b)| (a.conj() * *b).re)
+            .sum();
+        assert!((hamiltonian.expectation(&state) - expected).abs() < 1e-12);
+    }
+    #[test]
+    fn single_term_evolution_is_exact() {
+        let mut hamiltonian = PauliSum::new(3);
+        hamiltonian.add_term(0.8, &[(0, Pauli::X), (1, Pauli::Y), (2, Pauli::Z)]);
+        let exact = hamiltonian.matrix().expm(1.3);
+        let circuit = hamiltonian.trotter_circuit(1.3, 1, TrotterOrder::First);
+        assert!(circuit.unitary().approx_eq(&exact, 1e-12));
+    }
+    #[test]
+    fn trotter_error_shrinks_with_order_of_method() {
+        let hamiltonian = hamiltonian();
+        let time = 1.0;
+        let exact = hamiltonian.matrix().expm(time);
+        let error = |steps: usize, order: TrotterOrder| {
+            let circuit = hamiltonian.trotter_circuit(time, steps, order);
+            distance(&circuit.unitary(), &exact)
+        };
+        for steps in [4, 8, 16] {
+            let (first, second) = (
+                error(steps, TrotterOrder::First),
+                error(steps, TrotterOrder::Second),
+            );
+            assert!(second < first);
+            // Doubling the steps halves the first-order error and quarters the second.
+            let ratio_first = error(2 * steps, TrotterOrder::First) / first;
+            let ratio_second = error(2 * steps, TrotterOrder::Second) / second;
+            assert!((0.4..0.6).contains(&ratio_first), "{ratio_first}");
+            assert!((0.2..0.3).contains(&ratio_second), "{ratio_second}");
+        }
+        assert!(error(64, TrotterOrder::Second) < 1e-3);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::complex;
+use crate::complex::Complex;
+use crate::linalg::{hermitian_eigen, hermitian_function};
+use crate::matrix::QMatrix;
+use crate::{sample_outcome, QState, QuantumVec};
+
+const TOLERANCE: f64 = 1e-9;
+
+/// A generalized measurement, given by positive effect operators E_k that sum to the
+/// identity. Outcome k occurs with probability ⟨ψ|E_k|ψ⟩ and leaves the state
+/// √E_k|ψ⟩, renormalized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Povm {
+    effects: Vec<QMatrix>,
+    /// The square roots of the effects, which produce the post-measurement states.
+    roots: Vec<QMatrix>,
+}
+impl Povm {
+    /// Creates the measurement, panicking unless every effect is Hermitian and positive
+    /// semidefinite and the effects sum to the identity.
+    pub fn new(effects: Vec<QMatrix>) -> Povm {
+        assert!(!effects.is_empty());
+        let dims = effects[0].dims();
+        let mut sum = QMatrix::from_data(vec![Complex::zero(); dims * dims]);
+        for effect in &effects {
+            assert_eq!(effect.dims(), dims, "effects must have the same dimensions");
+            assert!(
+                effect.approx_eq(&effect.adjoint(), TOLERANCE),
+                "effects must be Hermitian"
+            );
+            let (values, _) = hermitian_eigen(effect);
+            assert!(
+                values[0] > -TOLERANCE,
+                "effects must be positive semidefinite"
+            );
+            sum = QMatrix::from_data(
+                sum.data_slice()
+                    .iter()
+                    .zip(effect.data_slice())
+                    .map(|(&a, &b)| a + b)
+                    .collect(),
+            );
+        }
+        assert!(
+            sum.approx_eq(&QMatrix::identity(dims), TOLERANCE),
+            "effects must sum to the identity"
+        );
+        let roots = effects
+            .iter()
+            .map(|effect| hermitian_function(effect, |value| complex!(value.max(0.0).sqrt(), 0.0)))
+            .collect();
+        Povm { effects, roots }
+    }
+    /// The measurement in the computational basis of `num_qubits` qubits.
+    pub fn computational_basis(num_qubits: usize) -> Povm {
+        let dims = 1 << num_qubits;
+        Povm::new(
+            (0..dims)
+                .map(|k| {
+                    let mut data = vec![Complex::zero(); dims * dims];
+                    data[k * dims + k] = Complex::one();
+                    QMatrix::from_data(data)
+                })
+                .collect(),
+        )
+    }
+    pub fn effects(&self) -> &[QMatrix] {
+        &self.effects
+    }
+    pub fn num_outcomes(&self) -> usize {
+        self.effects.len()
+    }
+    /// The number of qubits the effects act on.
+    pub fn num_qubits(&self) -> usize {
+        self.effects[0].dims().trailing_zeros() as usize
+    }
+    /// The probability of each outcome when measuring `qubits` of the state, with the
+    /// same qubit order as `QState::apply_on`.
+    pub fn probabilities(&self, state: &QState, qubits: &[usize]) -> Vec<f64> {
+        self.roots
+            .iter()
+            .map(|root| squared_norm(&state.apply_on(root, qubits)))
+            .collect()
+    }
+    /// Measures `qubits` of the state, replacing it with the post-measurement state,
+    /// and returns the index of the outcome.
+    pub fn measure(&self, state: &mut QState, qubits: &[usize]) -> usize {
+        let branches: Vec<QState> = self
+            .roots
+            .iter()
+            .map(|root| state.apply_on(root, qubits))
+            .collect();
+        let probs: Vec<f64> = branches.iter().map(squared_norm).collect();
+        let outcome = sample_outcome(&probs);
+        let norm = probs[outcome].sqrt();
+        *state = QState::from_data(
+            branches[outcome]
+                .data_slice()
+                .iter()
+                .map(|&z| z / norm)
+                .collect(),
+        );
+        outcome
+    }
+}
+fn squared_norm(state: &QState) -> f64 {
+    state.data_slice().iter().map(|z| z.prob()).sum()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use std::f64::consts::SQRT_2;
+
+    /// The optimal unambiguous discrimination of |0⟩ and |+⟩. Outcome 0 rules out |0⟩,
+    /// outcome 1 rules out |+⟩ and outcome 2 is inconclusive.
+    fn discrimination() -> Povm {
+        let k = SQRT_2 / (1.0 + SQRT_2);
+        let not_zero = QMatrix::from_data(vec![ZERO, ZERO, ZERO, complex!(k, 0.0)]);
+        let not_plus = (k / 2.0) * QMatrix::from_data(vec![ONE, -ONE, -ONE, ONE]);
+        let inconclusive = QMatrix::from_data(
+            I_GATE
+                .data_slice()
+                .iter()
+                .zip(not_zero.data_slice())
+                .zip(not_plus.data_slice())
+                .map(|((&i, &a), &b)| i - a - b)
+                .collect(),
+        );
+        Povm::new(vec![not_zero, not_plus, inconclusive])
+    }
+    #[test]
+    fn unambiguous_discrimination_never_errs() {
+        let povm = discrimination();
+        let zero = QState::basis_state(2, 0);
+        let plus = zero.apply_on(&H_GATE, &[1]);
+        let probs = povm.probabilities(&zero, &[1]);
+        let k = SQRT_2 / (1.0 + SQRT_2);
+        for (prob, expected) in probs.iter().zip([0.0, k / 2.0, 1.0 - k / 2.0]) {
+            assert!((prob - expected).abs() < 1e-12);
+        }
+        for _ in 0..50 {
+            let mut state = zero.clone();
+            assert_ne!(povm.measure(&mut state, &[1]), 0);
+            let mut state = plus.clone();
+            match povm.measure(&mut state, &[1]) {
+                0 => assert!(state.equals(&QState::basis_state(2, 2))),
+                outcome => assert_eq!(outcome, 2),
+            }
+        }
+    }
+    #[test]
+    fn computational_basis_povm_matches_projective_measurement() {
+        let povm = Povm::computational_basis(2);
+        let mut state = QState::basis_state(3, 0)
+            .apply_on(&H_GATE, &[0])
+            .apply_on(&CNOT_GATE, &[0, 2]);
+        let probs = povm.probabilities(&state, &[2, 0]);
+        for (prob, expected) in probs.iter().zip([0.5, 0.0, 0.0, 0.5]) {
+            assert!((prob - expected).abs() < 1e-12);
+        }
+        let outcome = povm.measure(&mut state, &[2, 0]);
+        assert!(state.equals(&QState::basis_state(3, [0, 0, 0, 0b101][outcome])));
+    }
+    #[test]
+    #[should_panic(expected = "effects must sum to the identity")]
+    fn incomplete_effects_panic() {
+        Povm::new(vec![QMatrix::from_data(vec![ONE, ZERO, ZERO, ZERO])]);
+    }
+}
@@ -0,0 +1,231 @@
+//! The quantum approximate optimization algorithm of Farhi, Goldstone and Gutmann,
+//! for problems whose cost is diagonal in the computational basis.
+use crate::circuit::Circuit;
+use crate::gradient::adjoint_gradient;
+use crate::minimize::{Minimizer, Minimum, Objective};
+use crate::pauli::{Pauli, PauliSum, TrotterOrder};
+use crate::{sample_outcome, Bitstring, QState};
+
+/// Alternates `layers` rounds of the cost evolution e^{-iγC} and the mixer
+/// e^{-iβΣX} on |+…+⟩. The angles are γ₁…γₚ followed by β₁…βₚ.
+#[derive(Debug, Clone)]
+pub struct Qaoa {
+    cost: PauliSum,
+    layers: usize,
+    initial: QState,
+}
+impl Qaoa {
+    /// The cost must be a sum of products of Z, which it minimizes.
+    pub fn new(cost: PauliSum, layers: usize) -> Qaoa {
+        assert!(layers > 0);
+        assert!(
+            cost.terms().iter().all(|term| term
+                .paulis
+                .iter()
+                .all(|&(_, p)| matches!(p, Pauli::I | Pauli::Z))),
+            "cost must be diagonal"
+        );
+        let initial = QState::basis_state(cost.num_qubits(), 0);
+        Qaoa {
+            cost,
+            layers,
+            initial,
+        }
+    }
+    /// Maximizes the total weight of the edges (a, b, weight) between vertices on
+    /// opposite sides of a cut, by minimizing Σ weight·(Z_a·Z_b − 1)/2.
+    pub fn max_cut(num_vertices: usize, edges: &[(usize, usize, f64)], layers: usize) -> Qaoa {
+        let mut cost = PauliSum::new(num_vertices);
+        for &(a, b, weight) in edges {
+            cost.add_term(weight / 2.0, &[(a, Pauli::Z), (b, Pauli::Z)])
+                .add_term(-weight / 2.0, &[]);
+        }
+        Qaoa::new(cost, layers)
+    }
+    /// Minimizes the Ising energy Σ fields[i]·Z_i + Σ coupling·Z_a·Z_b over the
+    /// couplings (a, b, coupling), where bit 0 is spin up.
+    pub fn ising(fields: &[f64], couplings: &[(usize, usize, f64)], layers: usize) -> Qaoa {
+        let mut cost = PauliSum::new(fields.len());
+        for (qubit, &field) in fields.iter().enumerate() {
+            cost.add_term(field, &[(qubit, Pauli::Z)]);
+        }
+        for &(a, b, coupling) in couplings {
+            cost.add_term(coupling, &[(a, Pauli::Z), (b, Pauli::Z)]);
+        }
+        Qaoa::new(cost, layers)
+    }
+    pub fn cost(&self) -> &PauliSum {
+        &self.cost
+    }
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+    /// The circuit for the given angles, with the index into the angles of each of its
+    /// parameterized gates.
+    fn layered_circuit(&self, angles: &[f64]) -> (Circuit, Vec<usize>) {
+        assert_eq!(
+            angles.len(),
+            2 * self.layers,
+            "expected γ and β for every layer"
+        );
+        let num_qubits = self.cost.num_qubits();
+        let mut circuit = Circuit::new(num_qubits);
+        let mut indices = Vec::new();
+        for qubit in 0..num_qubits {
+            circuit.h(qubit);
+        }
+        for layer in 0..self.layers {
+            let evolution = self
+                .cost
+                .trotter_circuit(angles[layer], 1, TrotterOrder::First);
+            for instruction in evolution.instructions() {
+                circuit.push(instruction.clone());
+            }
+            indices.resize(circuit.parameters().len(), layer);
+            for qubit in 0..num_qubits {
+                circuit.rx(2.0 * angles[self.layers + layer], qubit);
+            }
+            indices.resize(circuit.parameters().len(), self.layers + layer);
+        }
+        (circuit, indices)
+    }
+    pub fn circuit(&self, angles: &[f64]) -> Circuit {
+        self.layered_circuit(angles).0
+    }
+    pub fn state(&self, angles: &[f64]) -> QState {
+        self.circuit(angles).run(&self.initial)
+    }
+    /// The expected cost of measuring the state prepared with the given angles.
+    pub fn expectation(&self, angles: &[f64]) -> f64 {
+        self.cost.expectation(&self.state(angles))
+    }
+    /// The cost of a computational basis state, where bit i is the value of qubit i.
+    pub fn evaluate(&self, bitstring: &Bitstring) -> f64 {
+        assert_eq!(bitstring.len(), self.cost.num_qubits());
+        let value = bitstring.value();
+        self.cost
+            .terms()
+            .iter()
+            .map(|term| {
+                let flips = term
+                    .paulis
+                    .iter()
+                    .filter(|&&(q, p)| p == Pauli::Z && value >> q & 1 == 1)
+                    .count();
+                match flips % 2 {
+                    0 => term.coefficient,
+                    _ => -term.coefficient,
+                }
+            })
+            .sum()
+    }
+    /// Minimizes the expected cost over the angles, starting from the given ones.
+    pub fn run(&mut self, minimizer: &dyn Minimizer, initial: &[f64]) -> Minimum {
+        minimizer.minimize(self, initial)
+    }
+    /// Measures the state prepared with the given angles `shots` times and returns the
+    /// distinct outcomes of least cost, in increasing order of value.
+    pub fn best_bitstrings(&self, angles: &[f64], shots: usize) -> Vec<Bitstring> {
+        assert!(shots > 0);
+        let num_qubits = self.cost.num_qubits();
+        let probs = self.state(angles).probabilities();
+        let mut outcomes: Vec<usize> = (0..shots).map(|_| sample_outcome(&probs)).collect();
+        outcomes.sort_unstable();
+        outcomes.dedup();
+        let costs: Vec<f64> = outcomes
+            .iter()
+            .map(|&value| self.evaluate(&Bitstring::from_value(value, num_qubits)))
+            .collect();
+        let least = costs.iter().copied().fold(f64::INFINITY, f64::min);
+        outcomes
+            .into_iter()
+            .zip(costs)
+            .filter(|&(_, cost)| cost - least < 1e-9)
+            .map(|(value, _)| Bitstring::from_value(value, num_qubits))
+            .collect()
+    }
+}
+impl Objective for Qaoa {
+    fn value(&mut self, angles: &[f64]) -> f64 {
+        self.expectation(angles)
+    }
+    /// Every gate angle is a fixed multiple of one of the QAOA angles, so the chain rule
+    /// sums the gate gradients weighted by those multiples.
+    fn gradient(&mut self, angles: &[f64]) -> Vec<f64> {
+        let (circuit, indices) = self.layered_circuit(angles);
+        let multiples = self.circuit(&vec![1.0; angles.len()]).parameters();
+        let gate_gradient = adjoint_gradient(&circuit, &self.cost, &self.initial);
+        let mut gradient = vec![0.0; angles.len()];
+        for ((index, multiple), derivative) in indices.into_iter().zip(multiples).zip(gate_gradient)
+        {
+            gradient[index] += multiple * derivative;
+        }
+        gradient
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minimize::{Adam, NelderMead};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn cut_size(edges: &[(usize, usize, f64)], bitstring: &Bitstring) -> f64 {
+        edges
+            .iter()
+            .filter(|&&(a, b, _)| bitstring[a] != bitstring[b])
+            .map(|&(_, _, weight)| weight)
+            .sum()
+    }
+    #[test]
+    fn gradient_matches_finite_differences() {
+        let mut rng = StdRng::seed_from_u64(50);
+        let mut qaoa = Qaoa::ising(&[0.3, -0.7, 0.2], &[(0, 1, 1.0), (1, 2, -0.5)], 2);
+        let angles: Vec<f64> = (0..4).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let gradient = qaoa.gradient(&angles);
+        for k in 0..angles.len() {
+            let mut shifted = angles.clone();
+            shifted[k] += 1e-6;
+            let plus = qaoa.expectation(&shifted);
+            shifted[k] -= 2e-6;
+            let minus = qaoa.expectation(&shifted);
+            assert!((gradient[k] - (plus - minus) / 2e-6).abs() < 1e-6);
+        }
+    }
+    #[test]
+    fn evaluate_matches_expectation_in_basis_state() {
+        let qaoa = Qaoa::ising(&[0.3, -0.7, 0.2], &[(0, 1, 1.0), (0, 2, 0.4)], 1);
+        for value in 0..8 {
+            let cost = qaoa.evaluate(&Bitstring::from_value(value, 3));
+            let expected = qaoa.cost().expectation(&QState::basis_state(3, value));
+            assert!((cost - expected).abs() < 1e-12);
+        }
+    }
+    #[test]
+    fn solves_max_cut_on_ring() {
+        let edges: Vec<(usize, usize, f64)> = (0..6).map(|i| (i, (i + 1) % 6, 1.0)).collect();
+        let mut qaoa = Qaoa::max_cut(6, &edges, 2);
+        let minimum = qaoa.run(&NelderMead::new(), &[0.4, 0.8, 0.6, 0.3]);
+        // At depth p on a ring of more than 2p + 1 vertices the best expected cut is
+        // (2p + 1)/(2p + 2) of the maximum.
+        assert!((minimum.value + 5.0).abs() < 1e-6, "{minimum:?}");
+        let best = qaoa.best_bitstrings(&minimum.parameters, 200);
+        let values: Vec<usize> = best.iter().map(Bitstring::value).collect();
+        assert_eq!(values, [0b010101, 0b101010]);
+        assert!(best.iter().all(|b| cut_size(&edges, b) == 6.0));
+    }
+    #[test]
+    fn solves_max_cut_on_complete_graph() {
+        let edges: Vec<(usize, usize, f64)> = (0..4)
+            .flat_map(|a| (a + 1..4).map(move |b| (a, b, 1.0)))
+            .collect();
+        let mut qaoa = Qaoa::max_cut(4, &edges, 1);
+        let minimum = qaoa.run(&Adam::new().learning_rate(0.02), &[0.2, 0.2]);
+        let best = qaoa.best_bitstrings(&minimum.parameters, 200);
+        assert!(!best.is_empty());
+        for bitstring in &best {
+            assert_eq!(cut_size(&edges, bitstring), 4.0);
+            assert_eq!(qaoa.evaluate(bitstring), -4.0);
+        }
+    }
+}
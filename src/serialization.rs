@@ -0,0 +1,286 @@
+//! Serde support, enabled with the `serde` feature.
+//!
+//! The representations are meant to be stable across versions:
+//! - `Complex` is `[re, im]`.
+//! - `QMatrix` is `{"dims": n, "data": [...]}` with the n x n entries in row-major order.
+//! - `QState` is `{"num_qubits": n, "amplitudes": [...]}`.
+//! - `Qubit` is `{"alpha": [re, im], "beta": [re, im]}`.
+//! - `ClassicalBit` is `0` or `1`.
+//! - `Circuit` is `{"num_qubits": n, "num_clbits": m, "instructions": [...]}`.
+//!
+//! Deserializing validates the data. Matrix dimensions must be powers of two, states and
+//! qubits must be normalized, and circuit instructions must fit the circuit.
+use crate::circuit::{Circuit, Gate, Instruction};
+use crate::complex::Complex;
+use crate::matrix::QMatrix;
+use crate::{ClassicalBit, QState, QuantumVec, Qubit};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const NORMALIZATION_TOLERANCE: f64 = 1e-9;
+
+impl Serialize for Complex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        [self.re, self.im].serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Complex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [re, im] = <[f64; 2]>::deserialize(deserializer)?;
+        Ok(Complex { re, im })
+    }
+}
+#[derive(Serialize, Deserialize)]
+struct MatrixData {
+    dims: usize,
+    data: Vec<Complex>,
+}
+impl Serialize for QMatrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MatrixData {
+            dims: self.dims(),
+            data: self.data_slice().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for QMatrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let MatrixData { dims, data } = MatrixData::deserialize(deserializer)?;
+        if !dims.is_power_of_two() {
+            return Err(D::Error::custom(format!(
+                "matrix dimension {dims} is not a power of two"
+            )));
+        }
+        if dims.checked_mul(dims) != Some(data.len()) {
+            return Err(D::Error::custom(format!(
+                "expected {dims} x {dims} matrix entries, found {}",
+                data.len()
+            )));
+        }
+        Ok(QMatrix::from_data(data))
+    }
+}
+#[derive(Serialize, Deserialize)]
+struct StateData {
+    num_qubits: usize,
+    amplitudes: Vec<Complex>,
+}
+impl Serialize for QState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StateData {
+            num_qubits: self.num_qubits(),
+            amplitudes: self.state.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for QState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let StateData {
+            num_qubits,
+            amplitudes,
+        } = StateData::deserialize(deserializer)?;
+        let expected = 1usize
+            .checked_shl(num_qubits as u32)
+            .filter(|_| num_qubits < 64);
+        if expected != Some(amplitudes.len()) {
+            return Err(D::Error::custom(format!(
+                "expected 2^{num_qubits} amplitudes, found {}",
+                amplitudes.len()
+            )));
+        }
+        check_normalized::<D::Error>(&amplitudes)?;
+        Ok(QState::from_data(amplitudes))
+    }
+}
+#[derive(Serialize, Deserialize)]
+struct QubitData {
+    alpha: Complex,
+    beta: Complex,
+}
+impl Serialize for Qubit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        QubitData {
+            alpha: self.alpha,
+            beta: self.beta,
+        }
+        .serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Qubit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let QubitData { alpha, beta } = QubitData::deserialize(deserializer)?;
+        check_normalized::<D::Error>(&[alpha, beta])?;
+        Ok(Qubit { alpha, beta })
+    }
+}
+impl Serialize for ClassicalBit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self as u8).serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for ClassicalBit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(ClassicalBit::Off),
+            1 => Ok(ClassicalBit::On),
+            bit => Err(D::Error::custom(format!(
+                "classical bit must be 0 or 1, found {bit}"
+            ))),
+        }
+    }
+}
+#[derive(Serialize, Deserialize)]
+struct CircuitData {
+    num_qubits: usize,
+    num_clbits: usize,
+    instructions: Vec<Instruction>,
+}
+impl Serialize for Circuit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CircuitData {
+            num_qubits: self.num_qubits(),
+            num_clbits: self.num_clbits(),
+            instructions: self.instructions().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Circuit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = CircuitData::deserialize(deserializer)?;
+        let mut circuit = Circuit::with_clbits(data.num_qubits, data.num_clbits);
+        for instruction in data.instructions {
+            check_instruction::<D::Error>(&circuit, &instruction)?;
+            circuit.push(instruction);
+        }
+        Ok(circuit)
+    }
+}
+fn check_normalized<E: Error>(amplitudes: &[Complex]) -> Result<(), E> {
+    let norm: f64 = amplitudes.iter().map(|z| z.prob()).sum();
+    match (norm - 1.0).abs() < NORMALIZATION_TOLERANCE {
+        true => Ok(()),
+        false => Err(E::custom(format!(
+            "amplitudes are not normalized, squared norm is {norm}"
+        ))),
+    }
+}
+/// Checks the conditions `Circuit::push` asserts, so invalid input is an error
+/// rather than a panic.
+fn check_instruction<E: Error>(circuit: &Circuit, instruction: &Instruction) -> Result<(), E> {
+    match instruction {
+        Instruction::Gate { gate, qubits } => check_gate(circuit, gate, qubits)?,
+        Instruction::Measure { qubit, clbit } => {
+            if *qubit >= circuit.num_qubits() || *clbit >= circuit.num_clbits() {
+                return Err(E::custom(format!(
+                    "measurement of qubit {qubit} into bit {clbit} is out of range"
+                )));
+            }
+        }
+        Instruction::Conditional {
+            register,
+            value,
+            gate,
+            qubits,
+        } => {
+            check_gate(circuit, gate, qubits)?;
+            if register.bits().end > circuit.num_clbits() {
+                return Err(E::custom(format!(
+                    "classical bits {:?} are out of range",
+                    register.bits()
+                )));
+            }
+            if register.size() >= usize::BITS as usize || *value >> register.size() != 0 {
+                return Err(E::custom(format!(
+                    "value {value} does not fit in a register of {} bits",
+                    register.size()
+                )));
+            }
+        }
+        Instruction::Barrier => {}
+    }
+    Ok(())
+}
+fn check_gate<E: Error>(circuit: &Circuit, gate: &Gate, qubits: &[usize]) -> Result<(), E> {
+    if gate.num_qubits() != qubits.len() {
+        return Err(E::custom(format!(
+            "{gate:?} acts on {} qubits, but {} were given",
+            gate.num_qubits(),
+            qubits.len()
+        )));
+    }
+    if let Some(qubit) = qubits.iter().find(|&&q| q >= circuit.num_qubits()) {
+        return Err(E::custom(format!("qubit {qubit} is out of range")));
+    }
+    let mut sorted = qubits.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() != qubits.len() {
+        return Err(E::custom("gate qubits must be distinct"));
+    }
+    Ok(())
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::ClassicalRegister;
+    use crate::complex;
+    use crate::constants::*;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> T {
+        serde_json::from_str(&serde_json::to_string(value).unwrap()).unwrap()
+    }
+    #[test]
+    fn complex_serializes_as_pair() {
+        let z = complex!(0.5, -0.25);
+        assert_eq!(serde_json::to_string(&z).unwrap(), "[0.5,-0.25]");
+        assert_eq!(round_trip(&z), z);
+    }
+    #[test]
+    fn matrix_and_state_round_trip() {
+        assert_eq!(round_trip(&*H_GATE), *H_GATE);
+        let json = serde_json::to_string(&*CNOT_GATE).unwrap();
+        assert!(json.starts_with("{\"dims\":4,"));
+
+        let state =
+            QState::from_qubits(&[Qubit::new(C_IR2, complex!(0.0, IR2)), Qubit::new(ONE, ZERO)]);
+        assert!(round_trip(&state).equals(&state));
+        let qubit = Qubit::new(complex!(0.6, 0.0), complex!(0.0, 0.8));
+        assert!(QState::from(round_trip(&qubit)).equals(&QState::from(qubit)));
+        assert_eq!(serde_json::to_string(&ClassicalBit::On).unwrap(), "1");
+        assert!(matches!(round_trip(&ClassicalBit::Off), ClassicalBit::Off));
+    }
+    #[test]
+    fn circuit_round_trips() {
+        let mut circuit = Circuit::with_clbits(2, 1);
+        circuit
+            .h(0)
+            .rz(0.25, 1)
+            .cnot(0, 1)
+            .add(Gate::Unitary(T_GATE.clone()), &[1])
+            .barrier()
+            .measure(1, 0)
+            .add_if(ClassicalRegister::bit(0), 1, Gate::X, &[0]);
+        assert_eq!(round_trip(&circuit), circuit);
+    }
+    #[test]
+    fn invalid_data_is_rejected() {
+        let non_power_of_two =
+            r#"{"dims":3,"data":[[1,0],[0,0],[0,0],[0,0],[1,0],[0,0],[0,0],[0,0],[1,0]]}"#;
+        assert!(serde_json::from_str::<QMatrix>(non_power_of_two).is_err());
+        let wrong_length = r#"{"dims":2,"data":[[1,0],[0,0],[0,0]]}"#;
+        assert!(serde_json::from_str::<QMatrix>(wrong_length).is_err());
+        let unnormalized = r#"{"num_qubits":1,"amplitudes":[[1,0],[1,0]]}"#;
+        assert!(serde_json::from_str::<QState>(unnormalized).is_err());
+        let wrong_size = r#"{"num_qubits":2,"amplitudes":[[1,0],[0,0]]}"#;
+        assert!(serde_json::from_str::<QState>(wrong_size).is_err());
+        assert!(serde_json::from_str::<Qubit>(r#"{"alpha":[1,0],"beta":[1,0]}"#).is_err());
+        assert!(serde_json::from_str::<ClassicalBit>("2").is_err());
+        let out_of_range = r#"{"num_qubits":1,"num_clbits":0,"instructions":[{"Gate":{"gate":"Cnot","qubits":[0,1]}}]}"#;
+        assert!(serde_json::from_str::<Circuit>(out_of_range).is_err());
+        let wide_value = r#"{"num_qubits":1,"num_clbits":1,"instructions":[{"Conditional":{"register":{"start":0,"size":1},"value":2,"gate":"X","qubits":[0]}}]}"#;
+        assert!(serde_json::from_str::<Circuit>(wide_value).is_err());
+    }
+}
@@ -0,0 +1,561 @@
+use crate::complex::Complex;
+use crate::constants::{ONE, ZERO};
+use crate::matrix::QMatrix;
+use crate::{gate_mask, gate_offsets, ClassicalBit, QState, QuantumVec};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Amplitudes and entries smaller than this in magnitude are dropped from sparse storage.
+const ZERO_TOLERANCE: f64 = 1e-12;
+/// Matrices and states with at most this fraction of non-zero entries are stored sparsely.
+const SPARSE_FILL_RATIO: f64 = 0.1;
+/// A sparse state only becomes dense once its fill ratio exceeds this, so that states
+/// near the threshold do not convert back and forth after every gate.
+const DENSE_FILL_RATIO: f64 = 0.25;
+
+/// A square matrix in compressed sparse row form, with a compressed sparse column copy
+/// so that it can be applied to sparse states column by column.
+///
+/// `mul` and `kronecker` follow the same operand order as on `QMatrix`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMatrix {
+    dims: usize,
+    /// Row r holds the entries `row_offsets[r]..row_offsets[r + 1]` of `columns` and `values`.
+    row_offsets: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<Complex>,
+    /// Column c holds the entries `column_offsets[c]..column_offsets[c + 1]` of `rows` and
+    /// `column_values`.
+    column_offsets: Vec<usize>,
+    rows: Vec<usize>,
+    column_values: Vec<Complex>,
+}
+impl SparseMatrix {
+    fn from_rows(rows: impl Iterator<Item = Vec<(usize, Complex)>>) -> SparseMatrix {
+        let mut matrix = SparseMatrix {
+            dims: 0,
+            row_offsets: vec![0],
+            columns: Vec::new(),
+            values: Vec::new(),
+            column_offsets: Vec::new(),
+            rows: Vec::new(),
+            column_values: Vec::new(),
+        };
+        for row in rows {
+            for (col, value) in row {
+                if value.mag() > ZERO_TOLERANCE {
+                    matrix.columns.push(col);
+                    matrix.values.push(value);
+                }
+            }
+            matrix.row_offsets.push(matrix.columns.len());
+            matrix.dims += 1;
+        }
+        // Count the entries of each column, then place them in row order.
+        let mut offsets = vec![0; matrix.dims + 1];
+        for &col in &matrix.columns {
+            offsets[col + 1] += 1;
+        }
+        for col in 0..matrix.dims {
+            offsets[col + 1] += offsets[col];
+        }
+        matrix.rows = vec![0; matrix.nnz()];
+        matrix.column_values = vec![ZERO; matrix.nnz()];
+        let mut next = offsets.clone();
+        for row in 0..matrix.dims {
+            for entry in matrix.row_offsets[row]..matrix.row_offsets[row + 1] {
+                let slot = &mut next[matrix.columns[entry]];
+                matrix.rows[*slot] = row;
+                matrix.column_values[*slot] = matrix.values[entry];
+                *slot += 1;
+            }
+        }
+        matrix.column_offsets = offsets;
+        matrix
+    }
+    pub fn identity(dims: usize) -> SparseMatrix {
+        SparseMatrix::from_rows((0..dims).map(|row| vec![(row, ONE)]))
+    }
+    pub fn from_dense(matrix: &QMatrix) -> SparseMatrix {
+        let dims = matrix.dims();
+        SparseMatrix::from_rows(
+            matrix
+                .data_slice()
+                .chunks(dims)
+                .map(|row| row.iter().copied().enumerate().collect()),
+        )
+    }
+    pub fn to_dense(&self) -> QMatrix {
+        let mut data = vec![ZERO; self.dims * self.dims];
+        for row in 0..self.dims {
+            for (col, value) in self.row(row) {
+                data[row * self.dims + col] = value;
+            }
+        }
+        QMatrix::from_data(data)
+    }
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+    /// The number of stored non-zero entries.
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+    /// The fraction of entries that are non-zero.
+    pub fn fill_ratio(&self) -> f64 {
+        self.nnz() as f64 / (self.dims * self.dims) as f64
+    }
+    /// The column and value of each non-zero entry in a row.
+    pub fn row(&self, row: usize) -> impl Iterator<Item = (usize, Complex)> + '_ {
+        let range = self.row_offsets[row]..self.row_offsets[row + 1];
+        self.columns[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+    /// The row and value of each non-zero entry in a column.
+    pub fn column(&self, col: usize) -> impl Iterator<Item = (usize, Complex)> + '_ {
+        let range = self.column_offsets[col]..self.column_offsets[col + 1];
+        self.rows[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.column_values[range].iter().copied())
+    }
+    /// Calculates lhs * self, where `lhs` may also be a dense `QMatrix`.
+    pub fn mul(&self, lhs: &impl SparseOperand) -> SparseMatrix {
+        let lhs = lhs.as_sparse();
+        assert_eq!(self.dims, lhs.dims);
+        SparseMatrix::from_rows((0..self.dims).map(|row| {
+            let mut sum: BTreeMap<usize, Complex> = BTreeMap::new();
+            for (k, a) in lhs.row(row) {
+                for (col, b) in self.row(k) {
+                    *sum.entry(col).or_default() += a * b;
+                }
+            }
+            sum.into_iter().collect()
+        }))
+    }
+    /// Calculates the tensor product lhs ⊗ self, so `lhs` acts on the more significant qubits.
+    /// `lhs` may also be a dense `QMatrix`.
+    pub fn kronecker(&self, lhs: &impl SparseOperand) -> SparseMatrix {
+        let lhs: &SparseMatrix = &lhs.as_sparse();
+        SparseMatrix::from_rows((0..lhs.dims).flat_map(|lhs_row| {
+            (0..self.dims).map(move |row| {
+                lhs.row(lhs_row)
+                    .flat_map(|(lhs_col, a)| {
+                        self.row(row)
+                            .map(move |(col, b)| (lhs_col * self.dims + col, a * b))
+                    })
+                    .collect()
+            })
+        }))
+    }
+    pub fn adjoint(&self) -> SparseMatrix {
+        let mut rows: Vec<Vec<(usize, Complex)>> = vec![Vec::new(); self.dims];
+        for row in 0..self.dims {
+            for (col, value) in self.row(row) {
+                rows[col].push((row, value.conj()));
+            }
+        }
+        SparseMatrix::from_rows(rows.into_iter())
+    }
+    pub fn apply(&self, state: &QState) -> QState {
+        assert_eq!(self.dims, state.state.len());
+        QState::from_data(
+            (0..self.dims)
+                .map(|row| {
+                    self.row(row)
+                        .map(|(col, value)| value * state.state[col])
+                        .sum()
+                })
+                .collect(),
+        )
+    }
+    /// Applies the matrix to a sparse state by scattering each non-zero amplitude through
+    /// its column, so the cost depends only on the entries that are touched.
+    pub fn apply_sparse(&self, state: &SparseState) -> SparseState {
+        assert_eq!(self.dims, 1 << state.num_qubits);
+        let mut amplitudes: BTreeMap<usize, Complex> = BTreeMap::new();
+        for (&col, &amplitude) in &state.amplitudes {
+            for (row, value) in self.column(col) {
+                *amplitudes.entry(row).or_default() += value * amplitude;
+            }
+        }
+        amplitudes.retain(|_, value| value.mag() > ZERO_TOLERANCE);
+        SparseState {
+            num_qubits: state.num_qubits,
+            amplitudes,
+        }
+    }
+}
+/// A matrix that `SparseMatrix::mul` and `SparseMatrix::kronecker` accept as an operand.
+/// Dense operands are converted to sparse form for the operation.
+pub trait SparseOperand {
+    fn as_sparse(&self) -> Cow<'_, SparseMatrix>;
+}
+impl SparseOperand for SparseMatrix {
+    fn as_sparse(&self) -> Cow<'_, SparseMatrix> {
+        Cow::Borrowed(self)
+    }
+}
+impl SparseOperand for QMatrix {
+    fn as_sparse(&self) -> Cow<'_, SparseMatrix> {
+        Cow::Owned(SparseMatrix::from_dense(self))
+    }
+}
+impl SparseOperand for AdaptiveMatrix {
+    fn as_sparse(&self) -> Cow<'_, SparseMatrix> {
+        match self {
+            AdaptiveMatrix::Dense(matrix) => matrix.as_sparse(),
+            AdaptiveMatrix::Sparse(matrix) => matrix.as_sparse(),
+        }
+    }
+}
+impl From<&QMatrix> for SparseMatrix {
+    fn from(matrix: &QMatrix) -> Self {
+        SparseMatrix::from_dense(matrix)
+    }
+}
+impl From<&SparseMatrix> for QMatrix {
+    fn from(matrix: &SparseMatrix) -> Self {
+        matrix.to_dense()
+    }
+}
+/// A state that stores only its non-zero amplitudes, for states concentrated on a few
+/// computational basis states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseState {
+    num_qubits: usize,
+    amplitudes: BTreeMap<usize, Complex>,
+}
+impl SparseState {
+    pub fn basis_state(num_qubits: usize, index: usize) -> SparseState {
+        assert!(index < 1 << num_qubits);
+        SparseState {
+            num_qubits,
+            amplitudes: BTreeMap::from([(index, ONE)]),
+        }
+    }
+    pub fn from_dense(state: &QState) -> SparseState {
+        SparseState {
+            num_qubits: state.num_qubits(),
+            amplitudes: state
+                .state
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|(_, amplitude)| amplitude.mag() > ZERO_TOLERANCE)
+                .collect(),
+        }
+    }
+    pub fn to_dense(&self) -> QState {
+        let mut state = vec![ZERO; 1 << self.num_qubits];
+        for (&index, &amplitude) in &self.amplitudes {
+            state[index] = amplitude;
+        }
+        QState::from_data(state)
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.num_qubits
+    }
+    /// The amplitude of the basis state |index⟩.
+    pub fn amplitude(&self, index: usize) -> Complex {
+        self.amplitudes.get(&index).copied().unwrap_or(ZERO)
+    }
+    /// The number of stored non-zero amplitudes.
+    pub fn nnz(&self) -> usize {
+        self.amplitudes.len()
+    }
+    pub fn fill_ratio(&self) -> f64 {
+        self.nnz() as f64 / (1_usize << self.num_qubits) as f64
+    }
+    /// Applies a k-qubit gate with the same qubit order as `QState::apply_on`, visiting
+    /// only the groups of basis states that hold a non-zero amplitude.
+    pub fn apply_on(&self, gate: &QMatrix, qubits: &[usize]) -> SparseState {
+        let mask = gate_mask(gate, qubits, self.num_qubits);
+        let offsets = gate_offsets(qubits);
+        let bases: BTreeSet<usize> = self.amplitudes.keys().map(|i| i & !mask).collect();
+        let mut amplitudes = BTreeMap::new();
+        for base in bases {
+            let inputs: Vec<Complex> = offsets.iter().map(|&o| self.amplitude(base | o)).collect();
+            for (row, &offset) in offsets.iter().enumerate() {
+                let slice = &gate.data_slice()[(gate.dims() * row)..(gate.dims() * (row + 1))];
+                let value: Complex = slice.iter().zip(&inputs).map(|(&x, &y)| x * y).sum();
+                if value.mag() > ZERO_TOLERANCE {
+                    amplitudes.insert(base | offset, value);
+                }
+            }
+        }
+        SparseState {
+            num_qubits: self.num_qubits,
+            amplitudes,
+        }
+    }
+    pub fn measure(&mut self, qubit: usize) -> ClassicalBit {
+        assert!(qubit < self.num_qubits);
+        let mask = 1 << qubit;
+        let prob_1: f64 = self
+            .amplitudes
+            .iter()
+            .filter(|(&i, _)| i & mask != 0)
+            .map(|(_, z)| z.prob())
+            .sum();
+        let prob_0: f64 = self.amplitudes.values().map(|z| z.prob()).sum::<f64>() - prob_1;
+        let outcome = ClassicalBit::from_probs(prob_0, prob_1);
+        let (kept, norm) = match outcome {
+            ClassicalBit::Off => (0, prob_0.sqrt()),
+            ClassicalBit::On => (mask, prob_1.sqrt()),
+        };
+        self.amplitudes.retain(|&i, _| i & mask == kept);
+        for amplitude in self.amplitudes.values_mut() {
+            *amplitude /= norm;
+        }
+        outcome
+    }
+}
+/// A matrix stored densely or sparsely depending on its fill ratio.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdaptiveMatrix {
+    Dense(QMatrix),
+    Sparse(SparseMatrix),
+}
+impl AdaptiveMatrix {
+    pub fn new(matrix: QMatrix) -> AdaptiveMatrix {
+        AdaptiveMatrix::Dense(matrix).rebalanced()
+    }
+    pub fn to_dense(&self) -> QMatrix {
+        match self {
+            AdaptiveMatrix::Dense(matrix) => matrix.clone(),
+            AdaptiveMatrix::Sparse(matrix) => matrix.to_dense(),
+        }
+    }
+    pub fn fill_ratio(&self) -> f64 {
+        match self {
+            AdaptiveMatrix::Dense(matrix) => {
+                let nonzero = matrix
+                    .data_slice()
+                    .iter()
+                    .filter(|z| z.mag() > ZERO_TOLERANCE)
+                    .count();
+                nonzero as f64 / matrix.data_slice().len() as f64
+            }
+            AdaptiveMatrix::Sparse(matrix) => matrix.fill_ratio(),
+        }
+    }
+    fn rebalanced(self) -> AdaptiveMatrix {
+        match (&self, self.fill_ratio() <= SPARSE_FILL_RATIO) {
+            (AdaptiveMatrix::Dense(matrix), true) => {
+                AdaptiveMatrix::Sparse(SparseMatrix::from_dense(matrix))
+            }
+            (AdaptiveMatrix::Sparse(matrix), false) => AdaptiveMatrix::Dense(matrix.to_dense()),
+            _ => self,
+        }
+    }
+    /// Calculates lhs * self, sparsely unless both operands are dense.
+    pub fn mul(&self, lhs: &AdaptiveMatrix) -> AdaptiveMatrix {
+        match (self, lhs) {
+            (AdaptiveMatrix::Dense(a), AdaptiveMatrix::Dense(b)) => AdaptiveMatrix::Dense(a.mul(b)),
+            _ => AdaptiveMatrix::Sparse(self.as_sparse().mul(lhs)),
+        }
+        .rebalanced()
+    }
+    /// Calculates lhs ⊗ self, sparsely unless both operands are dense.
+    pub fn kronecker(&self, lhs: &AdaptiveMatrix) -> AdaptiveMatrix {
+        match (self, lhs) {
+            (AdaptiveMatrix::Dense(a), AdaptiveMatrix::Dense(b)) => {
+                AdaptiveMatrix::Dense(a.kronecker(b))
+            }
+            _ => AdaptiveMatrix::Sparse(self.as_sparse().kronecker(lhs)),
+        }
+        .rebalanced()
+    }
+    pub fn apply(&self, state: &QState) -> QState {
+        match self {
+            AdaptiveMatrix::Dense(matrix) => state.apply(matrix),
+            AdaptiveMatrix::Sparse(matrix) => matrix.apply(state),
+        }
+    }
+}
+/// A state stored densely or sparsely depending on its fill ratio, converting between
+/// the two as gates spread or concentrate its amplitudes.
+#[derive(Debug, Clone)]
+pub enum AdaptiveState {
+    Dense(QState),
+    Sparse(SparseState),
+}
+impl AdaptiveState {
+    pub fn basis_state(num_qubits: usize, index: usize) -> AdaptiveState {
+        AdaptiveState::Sparse(SparseState::basis_state(num_qubits, index)).rebalanced()
+    }
+    pub fn new(state: QState) -> AdaptiveState {
+        AdaptiveState::Dense(state).rebalanced()
+    }
+    pub fn to_dense(&self) -> QState {
+        match self {
+            AdaptiveState::Dense(state) => state.clone(),
+            AdaptiveState::Sparse(state) => state.to_dense(),
+        }
+    }
+    pub fn num_qubits(&self) -> usize {
+        match self {
+            AdaptiveState::Dense(state) => state.num_qubits(),
+            AdaptiveState::Sparse(state) => state.num_qubits(),
+        }
+    }
+    pub fn fill_ratio(&self) -> f64 {
+        match self {
+            AdaptiveState::Dense(state) => {
+                let nonzero = state
+                    .state
+                    .iter()
+                    .filter(|z| z.mag() > ZERO_TOLERANCE)
+                    .count();
+                nonzero as f64 / state.state.len() as f64
+            }
+            AdaptiveState::Sparse(state) => state.fill_ratio(),
+        }
+    }
+    fn rebalanced(self) -> AdaptiveState {
+        let fill_ratio = self.fill_ratio();
+        match self {
+            AdaptiveState::Dense(state) if fill_ratio <= SPARSE_FILL_RATIO => {
+                AdaptiveState::Sparse(SparseState::from_dense(&state))
+            }
+            AdaptiveState::Sparse(state) if fill_ratio > DENSE_FILL_RATIO => {
+                AdaptiveState::Dense(state.to_dense())
+            }
+            _ => self,
+        }
+    }
+    pub fn apply_on(&self, gate: &QMatrix, qubits: &[usize]) -> AdaptiveState {
+        match self {
+            AdaptiveState::Dense(state) => AdaptiveState::Dense(state.apply_on(gate, qubits)),
+            AdaptiveState::Sparse(state) => AdaptiveState::Sparse(state.apply_on(gate, qubits)),
+        }
+        .rebalanced()
+    }
+    pub fn measure(&mut self, qubit: usize) -> ClassicalBit {
+        let outcome = match self {
+            AdaptiveState::Dense(state) => state.measure(qubit),
+            AdaptiveState::Sparse(state) => state.measure(qubit),
+        };
+        // An empty sparse state is a placeholder that costs no allocation.
+        let empty = AdaptiveState::Sparse(SparseState {
+            num_qubits: 0,
+            amplitudes: BTreeMap::new(),
+        });
+        *self = std::mem::replace(self, empty).rebalanced();
+        outcome
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn dense_states_close(a: &QState, b: &QState) -> bool {
+        a.data_slice()
+            .iter()
+            .zip(b.data_slice())
+            .all(|(x, y)| (*x - *y).mag() < 1e-12)
+    }
+    #[test]
+    fn sparse_kronecker_and_mul_match_dense() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let random = QMatrix::haar_random(2, &mut rng);
+        let dense = CNOT_GATE
+            .kronecker(&random)
+            .mul(&I_GATE.kronecker(&SWAP_GATE));
+        let sparse = SparseMatrix::from(&*CNOT_GATE)
+            .kronecker(&SparseMatrix::from(&random))
+            .mul(&SparseMatrix::from(&*I_GATE).kronecker(&SparseMatrix::from(&*SWAP_GATE)));
+        assert!(sparse.to_dense().approx_eq(&dense, 1e-12));
+        let mixed = SparseMatrix::from(&*CNOT_GATE)
+            .kronecker(&random)
+            .mul(&I_GATE.kronecker(&SWAP_GATE));
+        assert_eq!(mixed, sparse);
+        assert!(sparse
+            .adjoint()
+            .to_dense()
+            .approx_eq(&dense.adjoint(), 1e-12));
+
+        let state = QState::basis_state(3, 0).apply(&QMatrix::haar_random(8, &mut rng));
+        assert!(dense_states_close(
+            &sparse.apply(&state),
+            &state.apply(&dense)
+        ));
+        let superposition = QState::basis_state(3, 0b010).apply_on(&H_GATE, &[2]);
+        assert!(dense_states_close(
+            &sparse
+                .apply_sparse(&SparseState::from_dense(&superposition))
+                .to_dense(),
+            &superposition.apply(&dense)
+        ));
+    }
+    #[test]
+    fn cnot_on_twelve_qubits_stays_sparse() {
+        let identity = SparseMatrix::identity(1 << 10);
+        let cnot = identity.kronecker(&SparseMatrix::from(&*CNOT_GATE));
+        assert_eq!(cnot.dims(), 1 << 12);
+        assert_eq!(cnot.nnz(), 1 << 12);
+        let state = SparseState::basis_state(12, 0b11 << 10);
+        let result = cnot.apply_sparse(&state);
+        assert_eq!(result.nnz(), 1);
+        assert_eq!(result.amplitude(0b10 << 10), ONE);
+    }
+    #[test]
+    fn sparse_state_gates_match_dense_state() {
+        let mut sparse = SparseState::basis_state(4, 0b0101);
+        let mut dense = QState::basis_state(4, 0b0101);
+        for (gate, qubits) in [
+            (&*H_GATE, vec![1]),
+            (&*CNOT_GATE, vec![1, 3]),
+            (&*T_GATE, vec![3]),
+            (&*SWAP_GATE, vec![0, 3]),
+        ] {
+            sparse = sparse.apply_on(gate, &qubits);
+            dense = dense.apply_on(gate, &qubits);
+        }
+        assert_eq!(sparse.nnz(), 2);
+        assert!(dense_states_close(&sparse.to_dense(), &dense));
+        let outcome = sparse.measure(0);
+        assert_eq!(sparse.nnz(), 1);
+        assert!(
+            (sparse
+                .amplitude(*sparse.amplitudes.keys().next().unwrap())
+                .mag()
+                - 1.0)
+                .abs()
+                < 1e-12
+        );
+        assert_eq!(
+            sparse.amplitudes.keys().next().unwrap() & 1,
+            outcome as usize
+        );
+    }
+    #[test]
+    fn adaptive_representations_follow_fill_ratio() {
+        let mut state = AdaptiveState::basis_state(4, 0);
+        assert!(matches!(state, AdaptiveState::Sparse(_)));
+        for qubit in 0..4 {
+            state = state.apply_on(&H_GATE, &[qubit]);
+        }
+        assert!(matches!(state, AdaptiveState::Dense(_)));
+        for qubit in 0..4 {
+            state.measure(qubit);
+        }
+        assert!(matches!(state, AdaptiveState::Sparse(_)));
+
+        let cnot = AdaptiveMatrix::new(CNOT_GATE.clone());
+        assert!(matches!(cnot, AdaptiveMatrix::Dense(_)));
+        let identity = AdaptiveMatrix::new(QMatrix::identity(16));
+        let wide = cnot.kronecker(&identity);
+        assert!(matches!(wide, AdaptiveMatrix::Sparse(_)));
+        assert!(wide
+            .to_dense()
+            .approx_eq(&CNOT_GATE.kronecker(&QMatrix::identity(16)), 1e-12));
+        let full = AdaptiveMatrix::new(H_GATE.clone());
+        assert!(matches!(full.kronecker(&full), AdaptiveMatrix::Dense(_)));
+    }
+}
@@ -0,0 +1,610 @@
+use crate::circuit::{Circuit, Gate, Instruction};
+use crate::decomposition::{euler_angles, kak_decomposition, EulerBasis};
+use crate::optimizer::{MergeSingleQubitGates, Pass, RemoveIdentities};
+use std::collections::VecDeque;
+
+/// Number of upcoming two-qubit gates considered by the routing lookahead.
+const EXTENDED_SET_SIZE: usize = 20;
+const EXTENDED_SET_WEIGHT: f64 = 0.5;
+const DECAY_INCREMENT: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BasisGate {
+    Rx,
+    Ry,
+    Rz,
+    Sx,
+    Cnot,
+    Cz,
+}
+/// Undirected connectivity between physical qubits.
+#[derive(Debug, Clone)]
+pub struct CouplingMap {
+    neighbours: Vec<Vec<usize>>,
+}
+impl CouplingMap {
+    pub fn new(num_qubits: usize, edges: &[(usize, usize)]) -> CouplingMap {
+        let mut neighbours = vec![Vec::new(); num_qubits];
+        for &(a, b) in edges {
+            assert!(a < num_qubits && b < num_qubits && a != b);
+            if !neighbours[a].contains(&b) {
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        }
+        CouplingMap { neighbours }
+    }
+    /// Qubits 0 - 1 - ... - (n - 1) in a line.
+    pub fn line(num_qubits: usize) -> CouplingMap {
+        let edges: Vec<(usize, usize)> = (1..num_qubits).map(|q| (q - 1, q)).collect();
+        CouplingMap::new(num_qubits, &edges)
+    }
+    pub fn ring(num_qubits: usize) -> CouplingMap {
+        let edges: Vec<(usize, usize)> =
+            (0..num_qubits).map(|q| (q, (q + 1) % num_qubits)).collect();
+        CouplingMap::new(num_qubits, &edges)
+    }
+    pub fn fully_connected(num_qubits: usize) -> CouplingMap {
+        let edges: Vec<(usize, usize)> = (0..num_qubits)
+            .flat_map(|a| ((a + 1)..num_qubits).map(move |b| (a, b)))
+            .collect();
+        CouplingMap::new(num_qubits, &edges)
+    }
+    pub fn num_qubits(&self) -> usize {
+        self.neighbours.len()
+    }
+    pub fn neighbours(&self, qubit: usize) -> &[usize] {
+        &self.neighbours[qubit]
+    }
+    pub fn are_connected(&self, a: usize, b: usize) -> bool {
+        self.neighbours[a].contains(&b)
+    }
+    /// The shortest path from `from` to `to`, including both ends.
+    pub fn shortest_path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut previous = vec![None; self.num_qubits()];
+        let mut queue = VecDeque::from([from]);
+        previous[from] = Some(from);
+        while let Some(qubit) = queue.pop_front() {
+            if qubit == to {
+                let mut path = vec![to];
+                while *path.last().unwrap() != from {
+                    path.push(previous[*path.last().unwrap()].unwrap());
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in &self.neighbours[qubit] {
+                if previous[next].is_none() {
+                    previous[next] = Some(qubit);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+    /// All-pairs hop distances, with `usize::MAX` for disconnected pairs.
+    fn distances(&self) -> Vec<Vec<usize>> {
+        (0..self.num_qubits())
+            .map(|from| {
+                let mut distance = vec![usize::MAX; self.num_qubits()];
+                let mut queue = VecDeque::from([from]);
+                distance[from] = 0;
+                while let Some(qubit) = queue.pop_front() {
+                    for &next in &self.neighbours[qubit] {
+                        if distance[next] == usize::MAX {
+                            distance[next] = distance[qubit] + 1;
+                            queue.push_back(next);
+                        }
+                    }
+                }
+                distance
+            })
+            .collect()
+    }
+}
+#[derive(Debug, Clone)]
+pub struct Transpiled {
+    /// The circuit on the physical qubits of the coupling map.
+    pub circuit: Circuit,
+    /// `initial_layout[q]` is the physical qubit holding logical qubit q at the start.
+    pub initial_layout: Vec<usize>,
+    /// `final_layout[q]` is the physical qubit holding logical qubit q at the end.
+    /// Qubits beyond the circuit's width are the unused physical qubits.
+    pub final_layout: Vec<usize>,
+    pub swaps: usize,
+}
+/// Rewrites a circuit into the basis gates and routes it onto the coupling map.
+///
+/// Two-qubit gates are first reduced to CNOTs with the KAK decomposition, then SWAPs
+/// are inserted until every CNOT acts on coupled qubits, and finally single-qubit runs
+/// are fused and emitted as Euler rotations in the basis. The result equals the
+/// original circuit up to global phase, followed by the permutation in `final_layout`.
+pub fn transpile(circuit: &Circuit, basis: &[BasisGate], coupling_map: &CouplingMap) -> Transpiled {
+    assert!(circuit.num_qubits() <= coupling_map.num_qubits());
+    let euler_basis = euler_basis(basis);
+    let entangler = if basis.contains(&BasisGate::Cnot) {
+        Gate::Cnot
+    } else if basis.contains(&BasisGate::Cz) {
+        Gate::Cz
+    } else {
+        panic!("the basis must contain CNOT or CZ");
+    };
+    let unrolled = unroll_to_cnots(circuit);
+    let (routed, final_layout, swaps) = route(&unrolled, coupling_map);
+
+    let mut translated = Circuit::with_clbits(routed.num_qubits(), routed.num_clbits());
+    for instruction in routed.instructions() {
+        rewrite_gate(
+            &mut translated,
+            instruction,
+            |translated, gate, qubits| match gate {
+                Gate::Swap => {
+                    let (a, b) = (qubits[0], qubits[1]);
+                    emit_cnot(translated, &entangler, a, b);
+                    emit_cnot(translated, &entangler, b, a);
+                    emit_cnot(translated, &entangler, a, b);
+                }
+                Gate::Cnot => emit_cnot(translated, &entangler, qubits[0], qubits[1]),
+                _ => {
+                    translated.add(gate.clone(), qubits);
+                }
+            },
+        );
+    }
+    let fused = MergeSingleQubitGates.run(&translated);
+    let mut native = Circuit::with_clbits(fused.num_qubits(), fused.num_clbits());
+    for instruction in fused.instructions() {
+        rewrite_gate(&mut native, instruction, |native, gate, qubits| {
+            match qubits.len() == 1 && !in_basis(gate, basis) {
+                true => {
+                    for gate in euler_angles(&gate.matrix(), euler_basis).gates() {
+                        native.add(gate, qubits);
+                    }
+                }
+                false => {
+                    native.add(gate.clone(), qubits);
+                }
+            }
+        });
+    }
+    Transpiled {
+        circuit: RemoveIdentities.run(&native),
+        initial_layout: (0..coupling_map.num_qubits()).collect(),
+        final_layout,
+        swaps,
+    }
+}
+fn euler_basis(basis: &[BasisGate]) -> EulerBasis {
+    let has = |gate| basis.contains(&gate);
+    if has(BasisGate::Rz) && has(BasisGate::Sx) {
+        EulerBasis::ZSX
+    } else if has(BasisGate::Rz) && has(BasisGate::Ry) {
+        EulerBasis::ZYZ
+    } else if has(BasisGate::Rz) && has(BasisGate::Rx) {
+        EulerBasis::ZXZ
+    } else if has(BasisGate::Rx) && has(BasisGate::Ry) {
+        EulerBasis::XYX
+    } else {
+        panic!("the basis cannot express arbitrary single-qubit gates");
+    }
+}
+fn in_basis(gate: &Gate, basis: &[BasisGate]) -> bool {
+    let basis_gate = match gate {
+        Gate::Rx(_) => BasisGate::Rx,
+        Gate::Ry(_) => BasisGate::Ry,
+        Gate::Rz(_) => BasisGate::Rz,
+        Gate::Sx => BasisGate::Sx,
+        Gate::Cnot => BasisGate::Cnot,
+        Gate::Cz => BasisGate::Cz,
+        _ => return false,
+    };
+    basis.contains(&basis_gate)
+}
+fn emit_cnot(circuit: &mut Circuit, entangler: &Gate, control: usize, target: usize) {
+    match entangler {
+        Gate::Cz => {
+            circuit.h(target).cz(control, target).h(target);
+        }
+        _ => {
+            circuit.cnot(control, target);
+        }
+    }
+}
+/// Rewrites every gate as single-qubit gates and CNOTs.
+fn unroll_to_cnots(circuit: &Circuit) -> Circuit {
+    let mut unrolled = Circuit::with_clbits(circuit.num_qubits(), circuit.num_clbits());
+    for instruction in circuit.instructions() {
+        rewrite_gate(&mut unrolled, instruction, |unrolled, gate, qubits| {
+            match (gate, qubits.len()) {
+                (_, 1) | (Gate::Cnot, _) => {
+                    unrolled.add(gate.clone(), qubits);
+                }
+                (Gate::Cz, _) => {
+                    unrolled
+                        .h(qubits[1])
+                        .cnot(qubits[0], qubits[1])
+                        .h(qubits[1]);
+                }
+                (Gate::Swap, _) => {
+                    let (a, b) = (qubits[0], qubits[1]);
+                    unrolled.cnot(a, b).cnot(b, a).cnot(a, b);
+                }
+                (_, 2) => {
+                    // Qubit 1 of the decomposed circuit is the gate's most significant qubit.
+                    let decomposed = kak_decomposition(&gate.matrix()).circuit();
+                    let mapping = [qubits[1], qubits[0]];
+                    for instruction in decomposed.instructions() {
+                        if let Instruction::Gate { gate, qubits } = instruction {
+                            let mapped: Vec<usize> = qubits.iter().map(|&q| mapping[q]).collect();
+                            unrolled.add(gate.clone(), &mapped);
+                        }
+                    }
+                }
+                _ => panic!("gates on more than two qubits are not supported"),
+            }
+        });
+    }
+    unrolled
+}
+/// Adds the gates `rewrite` replaces the instruction's gate with to `target`. The
+/// replacements of a conditional gate share its condition, and instructions without
+/// a gate are copied as they are.
+fn rewrite_gate(
+    target: &mut Circuit,
+    instruction: &Instruction,
+    rewrite: impl FnOnce(&mut Circuit, &Gate, &[usize]),
+) {
+    match instruction {
+        Instruction::Gate { gate, qubits } => rewrite(target, gate, qubits),
+        Instruction::Conditional {
+            register,
+            value,
+            gate,
+            qubits,
+        } => {
+            let mut replacement = Circuit::new(target.num_qubits());
+            rewrite(&mut replacement, gate, qubits);
+            for instruction in replacement.instructions() {
+                if let Instruction::Gate { gate, qubits } = instruction {
+                    target.add_if(*register, *value, gate.clone(), qubits);
+                }
+            }
+        }
+        _ => {
+            target.push(instruction.clone());
+        }
+    }
+}
+/// Routes the circuit with the SABRE heuristic (Li, Ding and Xie, 2019), starting
+/// from the trivial layout. Gates become executable once all earlier gates on their
+/// qubits have run; when no gate in this front layer is executable, the SWAP that
+/// most reduces the distance between the qubits of the front layer and of the next
+/// few gates is inserted. Instructions sharing a classical bit keep their order.
+/// Returns the physical circuit, the final layout and the number of SWAPs.
+fn route(circuit: &Circuit, coupling_map: &CouplingMap) -> (Circuit, Vec<usize>, usize) {
+    let num_physical = coupling_map.num_qubits();
+    let distances = coupling_map.distances();
+    let instructions = circuit.instructions();
+
+    let mut remaining_predecessors = vec![0; instructions.len()];
+    let mut successors: Vec<Vec<usize>> = vec![Vec::new(); instructions.len()];
+    // Wires are the qubits followed by the classical bits.
+    let mut last_on: Vec<Option<usize>> = vec![None; circuit.num_qubits() + circuit.num_clbits()];
+    for (index, instruction) in instructions.iter().enumerate() {
+        let wires: Vec<usize> = match instruction {
+            Instruction::Barrier => (0..circuit.num_qubits()).collect(),
+            _ => instruction
+                .qubits()
+                .iter()
+                .copied()
+                .chain(instruction.clbits().map(|c| circuit.num_qubits() + c))
+                .collect(),
+        };
+        for wire in wires {
+            if let Some(previous) = last_on[wire] {
+                if !successors[previous].contains(&index) {
+                    successors[previous].push(index);
+                    remaining_predecessors[index] += 1;
+                }
+            }
+            last_on[wire] = Some(index);
+        }
+    }
+    let mut front: Vec<usize> = (0..instructions.len())
+        .filter(|&index| remaining_predecessors[index] == 0)
+        .collect();
+
+    // layout maps virtual qubits to physical qubits, with extra virtual qubits as ancillas.
+    let mut layout: Vec<usize> = (0..num_physical).collect();
+    let mut routed = Circuit::with_clbits(num_physical, circuit.num_clbits());
+    let mut decay: Vec<f64> = vec![1.0; num_physical];
+    let mut swaps = 0;
+    let mut swaps_since_progress = 0;
+    let distance = |layout: &[usize], index: usize| {
+        let qubits = instructions[index].qubits();
+        distances[layout[qubits[0]]][layout[qubits[1]]] as f64
+    };
+
+    while !front.is_empty() {
+        let (executable, blocked): (Vec<usize>, Vec<usize>) =
+            front
+                .iter()
+                .partition(|&&index| match instructions[index].qubits() {
+                    &[a, b] => coupling_map.are_connected(layout[a], layout[b]),
+                    _ => true,
+                });
+        if !executable.is_empty() {
+            for &index in &executable {
+                match &instructions[index] {
+                    Instruction::Gate { gate, qubits } => {
+                        let mapped: Vec<usize> = qubits.iter().map(|&q| layout[q]).collect();
+                        routed.add(gate.clone(), &mapped);
+                    }
+                    Instruction::Measure { qubit, clbit } => {
+                        routed.measure(layout[*qubit], *clbit);
+                    }
+                    Instruction::Conditional {
+                        register,
+                        value,
+                        gate,
+                        qubits,
+                    } => {
+                        let mapped: Vec<usize> = qubits.iter().map(|&q| layout[q]).collect();
+                        routed.add_if(*register, *value, gate.clone(), &mapped);
+                    }
+                    Instruction::Barrier => {
+                        routed.barrier();
+                    }
+                }
+            }
+            front = blocked;
+            for index in executable {
+                for &successor in &successors[index] {
+                    remaining_predecessors[successor] -= 1;
+                    if remaining_predecessors[successor] == 0 {
+                        front.push(successor);
+                    }
+                }
+            }
+            decay.iter_mut().for_each(|d| *d = 1.0);
+            swaps_since_progress = 0;
+            continue;
+        }
+        if front
+            .iter()
+            .any(|&index| distance(&layout, index) == usize::MAX as f64)
+        {
+            panic!("the coupling map does not connect the qubits of a gate");
+        }
+
+        let mut swap_candidates: Vec<(usize, usize)> = Vec::new();
+        if swaps_since_progress > 2 * num_physical {
+            // Fall back to walking one qubit of the first blocked gate towards the other.
+            let qubits = instructions[front[0]].qubits();
+            let path = coupling_map
+                .shortest_path(layout[qubits[0]], layout[qubits[1]])
+                .unwrap();
+            swap_candidates.push((path[0], path[1]));
+        } else {
+            for &index in &front {
+                for &q in instructions[index].qubits() {
+                    for &neighbour in coupling_map.neighbours(layout[q]) {
+                        let swap = (layout[q].min(neighbour), layout[q].max(neighbour));
+                        if !swap_candidates.contains(&swap) {
+                            swap_candidates.push(swap);
+                        }
+                    }
+                }
+            }
+        }
+        let extended = extended_set(&front, &successors, &remaining_predecessors, instructions);
+        let score = |swap: &(usize, usize)| {
+            let mut trial = layout.clone();
+            swap_physical(&mut trial, swap.0, swap.1);
+            let front_cost: f64 = front
+                .iter()
+                .map(|&index| distance(&trial, index))
+                .sum::<f64>()
+                / front.len() as f64;
+            let extended_cost = match extended.len() {
+                0 => 0.0,
+                len => {
+                    extended
+                        .iter()
+                        .map(|&index| distance(&trial, index))
+                        .sum::<f64>()
+                        / len as f64
+                }
+            };
+            decay[swap.0].max(decay[swap.1]) * (front_cost + EXTENDED_SET_WEIGHT * extended_cost)
+        };
+        let &(a, b) = swap_candidates
+            .iter()
+            .min_by(|x, y| score(x).total_cmp(&score(y)))
+            .unwrap();
+        routed.swap(a, b);
+        swap_physical(&mut layout, a, b);
+        decay[a] += DECAY_INCREMENT;
+        decay[b] += DECAY_INCREMENT;
+        swaps += 1;
+        swaps_since_progress += 1;
+    }
+    (routed, layout, swaps)
+}
+/// The next two-qubit gates after the front layer, in dependency order.
+fn extended_set(
+    front: &[usize],
+    successors: &[Vec<usize>],
+    remaining_predecessors: &[usize],
+    instructions: &[Instruction],
+) -> Vec<usize> {
+    let mut remaining = remaining_predecessors.to_vec();
+    let mut queue: VecDeque<usize> = front.iter().copied().collect();
+    let mut extended = Vec::new();
+    while let Some(index) = queue.pop_front() {
+        for &successor in &successors[index] {
+            remaining[successor] -= 1;
+            if remaining[successor] == 0 {
+                if instructions[successor].qubits().len() == 2 {
+                    extended.push(successor);
+                    if extended.len() == EXTENDED_SET_SIZE {
+                        return extended;
+                    }
+                }
+                queue.push_back(successor);
+            }
+        }
+    }
+    extended
+}
+fn swap_physical(layout: &mut [usize], a: usize, b: usize) {
+    for physical in layout.iter_mut() {
+        if *physical == a {
+            *physical = b;
+        } else if *physical == b {
+            *physical = a;
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::QMatrix;
+    use crate::{QState, QuantumVec};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BASIS: [BasisGate; 3] = [BasisGate::Rz, BasisGate::Sx, BasisGate::Cnot];
+
+    /// The unitary of the original circuit, widened to the physical qubits and
+    /// followed by moving each logical qubit to its final physical qubit.
+    fn permuted_unitary(circuit: &Circuit, num_physical: usize, layout: &[usize]) -> QMatrix {
+        let mut widened = Circuit::new(num_physical);
+        for instruction in circuit.instructions() {
+            widened.push(instruction.clone());
+        }
+        let dims = 1 << num_physical;
+        let columns: Vec<Vec<_>> = (0..dims)
+            .map(|col| {
+                let state = widened.run(&QState::basis_state(num_physical, col));
+                let mut permuted = vec![crate::constants::ZERO; dims];
+                for (index, &amplitude) in state.data_slice().iter().enumerate() {
+                    let moved: usize = (0..num_physical)
+                        .filter(|&q| index >> q & 1 == 1)
+                        .map(|q| 1 << layout[q])
+                        .sum();
+                    permuted[moved] = amplitude;
+                }
+                permuted
+            })
+            .collect();
+        QMatrix::from_data(
+            (0..dims * dims)
+                .map(|i| columns[i % dims][i / dims])
+                .collect(),
+        )
+    }
+    fn assert_transpiled(
+        circuit: &Circuit,
+        basis: &[BasisGate],
+        coupling_map: &CouplingMap,
+    ) -> Transpiled {
+        let transpiled = transpile(circuit, basis, coupling_map);
+        for instruction in transpiled.circuit.instructions() {
+            if let Instruction::Gate { gate, qubits } = instruction {
+                assert!(in_basis(gate, basis), "{gate:?} is not in the basis");
+                if qubits.len() == 2 {
+                    assert!(coupling_map.are_connected(qubits[0], qubits[1]));
+                }
+            }
+        }
+        let expected =
+            permuted_unitary(circuit, coupling_map.num_qubits(), &transpiled.final_layout);
+        assert!(transpiled
+            .circuit
+            .unitary()
+            .equals_up_to_phase(&expected, 1e-8));
+        transpiled
+    }
+    fn random_circuit(num_qubits: usize, gates: usize, rng: &mut impl Rng) -> Circuit {
+        let mut circuit = Circuit::new(num_qubits);
+        for _ in 0..gates {
+            let a = rng.gen_range(0..num_qubits);
+            let b = (a + rng.gen_range(1..num_qubits)) % num_qubits;
+            match rng.gen_range(0..6) {
+                0 => circuit.h(a),
+                1 => circuit.rz(rng.gen_range(-3.0..3.0), a),
+                2 => circuit.cnot(a, b),
+                3 => circuit.cz(a, b),
+                4 => circuit.swap(a, b),
+                _ => circuit.add(Gate::Unitary(QMatrix::haar_random(4, rng)), &[a, b]),
+            };
+        }
+        circuit
+    }
+    #[test]
+    fn transpiling_onto_a_line_inserts_swaps_for_distant_cnots() {
+        let mut circuit = Circuit::new(4);
+        circuit
+            .h(0)
+            .cnot(0, 3)
+            .t(3)
+            .cnot(1, 3)
+            .rx(0.3, 2)
+            .cnot(2, 0);
+        let transpiled = assert_transpiled(&circuit, &BASIS, &CouplingMap::line(4));
+        assert!(transpiled.swaps > 0);
+    }
+    #[test]
+    fn transpiling_onto_a_fully_connected_map_keeps_the_layout() {
+        let mut circuit = Circuit::new(3);
+        circuit.h(0).cnot(0, 2).cnot(2, 1).add(Gate::Y, &[1]);
+        let transpiled = assert_transpiled(&circuit, &BASIS, &CouplingMap::fully_connected(3));
+        assert_eq!(transpiled.swaps, 0);
+        assert_eq!(transpiled.final_layout, vec![0, 1, 2]);
+    }
+    #[test]
+    fn transpiling_random_circuits_preserves_unitary_modulo_permutation() {
+        let mut rng = StdRng::seed_from_u64(30);
+        for _ in 0..5 {
+            let circuit = random_circuit(4, 12, &mut rng);
+            assert_transpiled(&circuit, &BASIS, &CouplingMap::line(4));
+            assert_transpiled(&circuit, &BASIS, &CouplingMap::ring(4));
+        }
+    }
+    #[test]
+    fn transpiling_into_cz_basis_with_spare_physical_qubits() {
+        let mut rng = StdRng::seed_from_u64(31);
+        let circuit = random_circuit(3, 10, &mut rng);
+        let basis = [BasisGate::Rz, BasisGate::Ry, BasisGate::Cz];
+        assert_transpiled(&circuit, &basis, &CouplingMap::line(5));
+    }
+    #[test]
+    fn transpiling_conditional_gates_keeps_them_after_their_measurements() {
+        let mut circuit = Circuit::new(3);
+        let register = circuit.add_register(1);
+        circuit
+            .x(0)
+            .x(1)
+            .measure(1, 0)
+            .add_if(register, 1, Gate::Cnot, &[0, 2]);
+        let transpiled = transpile(&circuit, &BASIS, &CouplingMap::line(3));
+        for instruction in transpiled.circuit.instructions() {
+            if let Instruction::Conditional { gate, qubits, .. } = instruction {
+                assert!(in_basis(gate, &BASIS), "{gate:?} is not in the basis");
+                if qubits.len() == 2 {
+                    assert!(CouplingMap::line(3).are_connected(qubits[0], qubits[1]));
+                }
+            }
+        }
+        let (state, clbits) = transpiled.circuit.execute(&QState::basis_state(3, 0));
+        assert_eq!(clbits, vec![crate::ClassicalBit::On]);
+        let target = transpiled.final_layout[2];
+        assert!((state.bloch_vector(target).2 + 1.0).abs() < 1e-9);
+    }
+    #[test]
+    fn shortest_path_on_a_ring_goes_the_short_way() {
+        let ring = CouplingMap::ring(6);
+        assert_eq!(ring.shortest_path(0, 4), Some(vec![0, 5, 4]));
+    }
+}
@@ -0,0 +1,126 @@
+//! The variational quantum eigensolver, which minimizes the energy of a Hamiltonian
+//! over the states prepared by a parameterized circuit.
+use crate::circuit::Circuit;
+use crate::gradient::adjoint_gradient;
+use crate::minimize::{Minimizer, Minimum, Objective};
+use crate::pauli::PauliSum;
+use crate::QState;
+
+/// A circuit of `layers` entangling layers, each RY and RZ on every qubit followed by
+/// a chain of CNOTs, and a final layer of rotations. All angles start at zero.
+pub fn hardware_efficient_ansatz(num_qubits: usize, layers: usize) -> Circuit {
+    let mut circuit = Circuit::new(num_qubits);
+    let rotations = |circuit: &mut Circuit| {
+        for qubit in 0..num_qubits {
+            circuit.ry(0.0, qubit).rz(0.0, qubit);
+        }
+    };
+    for _ in 0..layers {
+        rotations(&mut circuit);
+        for qubit in 1..num_qubits {
+            circuit.cnot(qubit - 1, qubit);
+        }
+    }
+    rotations(&mut circuit);
+    circuit
+}
+#[derive(Debug, Clone)]
+pub struct Vqe {
+    hamiltonian: PauliSum,
+    ansatz: Circuit,
+    initial: QState,
+}
+impl Vqe {
+    /// The ansatz is applied to |0…0⟩ and its parameters are those of
+    /// `Circuit::parameters`.
+    pub fn new(hamiltonian: PauliSum, ansatz: Circuit) -> Vqe {
+        assert_eq!(hamiltonian.num_qubits(), ansatz.num_qubits());
+        let initial = QState::basis_state(ansatz.num_qubits(), 0);
+        Vqe {
+            hamiltonian,
+            ansatz,
+            initial,
+        }
+    }
+    pub fn hamiltonian(&self) -> &PauliSum {
+        &self.hamiltonian
+    }
+    pub fn ansatz(&self) -> &Circuit {
+        &self.ansatz
+    }
+    /// The state the ansatz prepares with the given parameters.
+    pub fn state(&self, parameters: &[f64]) -> QState {
+        self.ansatz.with_parameters(parameters).run(&self.initial)
+    }
+    pub fn energy(&self, parameters: &[f64]) -> f64 {
+        self.hamiltonian.expectation(&self.state(parameters))
+    }
+    /// Minimizes the energy starting from the given parameters.
+    pub fn run(&mut self, minimizer: &dyn Minimizer, initial: &[f64]) -> Minimum {
+        minimizer.minimize(self, initial)
+    }
+}
+impl Objective for Vqe {
+    fn value(&mut self, parameters: &[f64]) -> f64 {
+        self.energy(parameters)
+    }
+    fn gradient(&mut self, parameters: &[f64]) -> Vec<f64> {
+        let circuit = self.ansatz.with_parameters(parameters);
+        adjoint_gradient(&circuit, &self.hamiltonian, &self.initial)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minimize::{Adam, NelderMead, Spsa};
+    use crate::pauli::Pauli;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::f64::consts::PI;
+
+    /// The energy error that chemistry calls accurate, 1 kcal/mol in hartree.
+    const CHEMICAL_ACCURACY: f64 = 1.6e-3;
+
+    /// The electronic Hamiltonian of H2 in the STO-3G basis at a bond length of
+    /// 0.735 Å, reduced to two qubits by the parity mapping.
+    fn hydrogen() -> PauliSum {
+        let mut hamiltonian = PauliSum::new(2);
+        hamiltonian
+            .add_term(-1.052373245772859, &[])
+            .add_term(0.39793742484318045, &[(1, Pauli::Z)])
+            .add_term(-0.39793742484318045, &[(0, Pauli::Z)])
+            .add_term(-0.01128010425623538, &[(0, Pauli::Z), (1, Pauli::Z)])
+            .add_term(0.18093119978423156, &[(0, Pauli::X), (1, Pauli::X)]);
+        hamiltonian
+    }
+    #[test]
+    fn ansatz_has_two_rotations_per_qubit_and_layer() {
+        let ansatz = hardware_efficient_ansatz(3, 2);
+        assert_eq!(ansatz.parameters().len(), 2 * 3 * 3);
+        assert_eq!(ansatz.gate_count(), 2 * 3 * 3 + 2 * 2);
+    }
+    #[test]
+    fn every_minimizer_finds_hydrogen_ground_energy() {
+        let hamiltonian = hydrogen();
+        let exact = hamiltonian.matrix().hermitian_eigen().0[0];
+        assert!((exact + 1.857275030202378).abs() < 1e-9);
+        let minimizers: [Box<dyn Minimizer>; 3] = [
+            Box::new(NelderMead::new()),
+            Box::new(Spsa::new().seed(7).learning_rate(1.0).iterations(3000)),
+            Box::new(Adam::new()),
+        ];
+        let mut rng = StdRng::seed_from_u64(49);
+        let mut vqe = Vqe::new(hamiltonian, hardware_efficient_ansatz(2, 1));
+        for minimizer in &minimizers {
+            let initial: Vec<f64> = (0..vqe.ansatz().parameters().len())
+                .map(|_| rng.gen_range(-PI..PI))
+                .collect();
+            let minimum = vqe.run(minimizer.as_ref(), &initial);
+            assert!(
+                (minimum.value - exact).abs() < CHEMICAL_ACCURACY,
+                "{minimum:?}"
+            );
+            assert!((vqe.energy(&minimum.parameters) - minimum.value).abs() < 1e-12);
+        }
+    }
+}